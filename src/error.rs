@@ -0,0 +1,130 @@
+use std::fmt;
+
+use crate::graphics::framebuffer::{AttachmentType, FramebufferStatus};
+use crate::graphics::texture::TextureFormat;
+
+// Errors returned by texture and framebuffer operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    // A texture was created with a zero width or height, or larger than MAX_TEXTURE_SIZE.
+    InvalidDimensions { width: u32, height: u32 },
+    // The format of a texture can't be used for the attachment.
+    FormatMismatch { attachment: AttachmentType, format: TextureFormat },
+    // The length of a pixel buffer doesn't match the size of the texture.
+    SizeMismatch { expected: usize, actual: usize },
+    // A region of texels doesn't fit in the texture.
+    RegionOutOfBounds { x: u32, y: u32, width: u32, height: u32 },
+    // The attachment doesn't exist, e.g. a color attachment index out of range.
+    InvalidAttachment(AttachmentType),
+    // Nothing is attached to the attachment.
+    MissingAttachment(AttachmentType),
+    // The operation isn't supported for textures of the format.
+    UnsupportedFormat(TextureFormat),
+    IncompleteFramebuffer(FramebufferStatus),
+    // A texture array or volume was created without layers.
+    InvalidLayerCount(u32),
+    // A layer index out of range of a texture array or volume with `count` layers.
+    InvalidLayer { index: usize, count: usize },
+    // The format of a texture doesn't match the texture it's put into, e.g. a face of a cube map.
+    TextureFormatMismatch { expected: TextureFormat, actual: TextureFormat },
+    // The size of a texture doesn't match the texture it's put into.
+    TextureSizeMismatch { expected: (u32, u32), actual: (u32, u32) },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::InvalidDimensions { width, height } => {
+                write!(f, "invalid texture dimensions {}x{}", width, height)
+            },
+            RenderError::FormatMismatch { attachment, format } => {
+                write!(f, "format {:?} can't be attached to {:?}", format, attachment)
+            },
+            RenderError::SizeMismatch { expected, actual } => {
+                write!(f, "expected {} bytes of pixels, got {}", expected, actual)
+            },
+            RenderError::RegionOutOfBounds { x, y, width, height } => {
+                write!(f, "region {}x{} at ({}, {}) is out of the texture", width, height, x, y)
+            },
+            RenderError::InvalidAttachment(attachment) => {
+                write!(f, "invalid attachment {:?}", attachment)
+            },
+            RenderError::MissingAttachment(attachment) => {
+                write!(f, "nothing is attached to {:?}", attachment)
+            },
+            RenderError::UnsupportedFormat(format) => {
+                write!(f, "unsupported texture format {:?}", format)
+            },
+            RenderError::IncompleteFramebuffer(status) => {
+                write!(f, "incomplete framebuffer: {:?}", status)
+            },
+            RenderError::InvalidLayerCount(count) => {
+                write!(f, "invalid layer count {}", count)
+            },
+            RenderError::InvalidLayer { index, count } => {
+                write!(f, "layer {} is out of range of {} layers", index, count)
+            },
+            RenderError::TextureFormatMismatch { expected, actual } => {
+                write!(f, "expected a texture of format {:?}, got {:?}", expected, actual)
+            },
+            RenderError::TextureSizeMismatch { expected, actual } => {
+                write!(f, "expected a texture of {}x{}, got {}x{}", expected.0, expected.1, actual.0, actual.1)
+            },
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+// Errors returned by image loading and saving.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    // The data ends before the image does.
+    Truncated,
+    // The image type of the file isn't supported, e.g. a compressed color-mapped or Huffman encoded image.
+    UnsupportedImageType(u8),
+    // The number of bits of a pixel or a color map entry isn't supported.
+    UnsupportedPixelDepth(u8),
+    // A color-mapped pixel indexes outside of the color map.
+    InvalidColorIndex(u32),
+    // The image is too large for the file format.
+    TooLarge { width: u32, height: u32 },
+    Texture(RenderError),
+    Io(std::io::ErrorKind),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Truncated => write!(f, "image data is truncated"),
+            ImageError::UnsupportedImageType(image_type) => {
+                write!(f, "unsupported image type {}", image_type)
+            },
+            ImageError::UnsupportedPixelDepth(depth) => {
+                write!(f, "unsupported pixel depth {}", depth)
+            },
+            ImageError::InvalidColorIndex(index) => {
+                write!(f, "color index {} is out of the color map", index)
+            },
+            ImageError::TooLarge { width, height } => {
+                write!(f, "image of {}x{} is too large", width, height)
+            },
+            ImageError::Texture(error) => write!(f, "{}", error),
+            ImageError::Io(kind) => write!(f, "I/O error: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<RenderError> for ImageError {
+    fn from(error: RenderError) -> Self {
+        ImageError::Texture(error)
+    }
+}
+
+impl From<std::io::Error> for ImageError {
+    fn from(error: std::io::Error) -> Self {
+        ImageError::Io(error.kind())
+    }
+}
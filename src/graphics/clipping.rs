@@ -0,0 +1,78 @@
+use crate::math::preclude::Vec4;
+
+use super::shader_context::ShaderContext;
+
+// A vertex in homogeneous clip space together with its varyings.
+#[derive(Debug, Clone)]
+pub struct ClipVertex {
+    pub position: Vec4,
+    pub context: ShaderContext,
+}
+
+/*
+ * The six planes of the view frustum in clip space, -w <= x, y, z <= w.
+ * The signed distance of a position to a plane is the dot product of the
+ * position with the plane, positive on the inner side.
+ */
+const FRUSTUM_PLANES: [Vec4; 6] = [
+    Vec4 { x: 1., y: 0., z: 0., w: 1. },    // left
+    Vec4 { x: -1., y: 0., z: 0., w: 1. },   // right
+    Vec4 { x: 0., y: 1., z: 0., w: 1. },    // bottom
+    Vec4 { x: 0., y: -1., z: 0., w: 1. },   // top
+    Vec4 { x: 0., y: 0., z: 1., w: 1. },    // near
+    Vec4 { x: 0., y: 0., z: -1., w: 1. },   // far
+];
+
+#[inline]
+fn is_inside(position: Vec4) -> bool {
+    FRUSTUM_PLANES.iter().all(|plane| plane.dot(position) >= 0.)
+}
+
+// Whether every vertex of the triangle is inside the frustum, so no clipping is needed.
+#[inline]
+pub fn is_triangle_inside(positions: [Vec4; 3]) -> bool {
+    positions.iter().all(|position| is_inside(*position))
+}
+
+// Sutherland–Hodgman: clip the polygon against one plane.
+fn clip_against_plane(polygon: &[ClipVertex], plane: Vec4) -> Vec<ClipVertex> {
+    let mut result = Vec::with_capacity(polygon.len() + 1);
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let current_distance = plane.dot(current.position);
+        let next_distance = plane.dot(next.position);
+        if current_distance >= 0. {
+            result.push(current.clone());
+        }
+        if (current_distance >= 0.) != (next_distance >= 0.) {
+            // The edge crosses the plane, emit the intersection.
+            let t = current_distance / (current_distance - next_distance);
+            result.push(ClipVertex {
+                position: current.position.lerp(next.position, t),
+                context: current.context.lerp(&next.context, t),
+            });
+        }
+    }
+    result
+}
+
+/*
+ * Clip a triangle against the six planes of the view frustum in homogeneous
+ * clip space. Returns the vertices of the resulting convex polygon in the
+ * original winding, empty if the triangle is completely outside. Varyings of
+ * the new vertices are interpolated linearly in clip space. Flat varyings of
+ * every vertex take the value of the first vertex, which may be clipped away.
+ */
+pub fn clip_triangle(vertices: [ClipVertex; 3]) -> Vec<ClipVertex> {
+    let mut polygon = vertices.to_vec();
+    for plane in FRUSTUM_PLANES.iter() {
+        polygon = clip_against_plane(&polygon, *plane);
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+    for vertex in polygon.iter_mut() {
+        vertex.context.copy_flat(&vertices[0].context);
+    }
+    polygon
+}
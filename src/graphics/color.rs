@@ -20,3 +20,66 @@ pub fn convert_to_srgb_color(value: f32) -> f32 {
 pub fn convert_to_linear_color(value: f32) -> f32 {
     value.powf(GAMMA)
 }
+
+/*
+ * Convert to the bits of an IEEE 754 half-precision float, rounding to the
+ * nearest even value. Values too large for a half become infinities, values
+ * too small become subnormals or zeros.
+ */
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinities, NaNs keep a quiet NaN payload.
+        return match mantissa {
+            0 => sign | 0x7c00,
+            _ => sign | 0x7e00,
+        };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // Bits of the half mantissa, including the implicit leading bit for subnormals.
+    let (half, shift) = match exponent <= 0 {
+        true => {
+            if exponent < -10 {
+                return sign;
+            }
+            (0, (14 - exponent) as u32)
+        },
+        false => (((exponent as u32) << 10), 13),
+    };
+    let mantissa = match exponent <= 0 {
+        true => mantissa | 0x80_0000,
+        false => mantissa,
+    };
+    let mut half = half | (mantissa >> shift);
+    let remainder = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    // A carry into the exponent correctly rounds up to the next binade or to infinity.
+    if remainder > halfway || (remainder == halfway && half & 1 == 1) {
+        half += 1;
+    }
+    sign | half as u16
+}
+
+pub fn f16_to_f32(value: u16) -> f32 {
+    let sign = ((value & 0x8000) as u32) << 16;
+    let exponent = ((value >> 10) & 0x1f) as u32;
+    let mantissa = (value & 0x3ff) as u32;
+    let bits = match exponent {
+        0 => {
+            // Zeros and subnormals, mantissa * 2^-24.
+            let magnitude = mantissa as f32 * 2f32.powi(-24);
+            return f32::from_bits(sign | magnitude.to_bits());
+        },
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
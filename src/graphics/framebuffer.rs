@@ -1,12 +1,53 @@
-use super::{texture::{Texture, TextureFormat}, color::f32_to_u8};
-use crate::math::{utility::f32_clamp01, preclude::u32_min};
+use super::texture::{Texture, TextureFormat};
+use super::render_state::{BlendState, ColorWriteMask};
+use crate::math::preclude::{u32_min, Vec4};
+use crate::vec4_zero;
+use crate::error::RenderError;
 
-static mut CLEAR_COLOR: [u8; 4] = [0; 4];
+// Number of color attachments of a framebuffer, Color(0) to Color(7).
+pub const MAX_COLOR_ATTACHMENTS: usize = 8;
 
-#[derive(PartialEq, Eq, Debug)]
+/*
+ * Completeness of a framebuffer. Formats are checked by attach_texture() and
+ * attachments of different sizes are allowed, the framebuffer takes the
+ * smallest one, so a framebuffer is only incomplete without attachments.
+ */
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum FramebufferStatus {
+    Complete,
+    // No texture is attached.
+    MissingAttachment,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum AttachmentType {
-    Color,
+    Color(usize),
     Depth,
+    Stencil,
+}
+
+/*
+ * Maps NDC to window coordinates: x and y from [-1, 1] to the rectangle
+ * starting at (x, y) with the origin at the bottom-left corner, depth from
+ * [-1, 1] to [min_depth, max_depth].
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+// A rectangle of pixels, the origin is at the bottom-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
 }
 
 #[allow(dead_code)]
@@ -14,8 +55,22 @@ pub enum AttachmentType {
 pub struct FrameBuffer {
     width: u32,
     height: u32,
-    color_buffer: Option<Box<Texture>>,
+    color_buffers: [Option<Box<Texture>>; MAX_COLOR_ATTACHMENTS],
     depth_buffer: Option<Box<Texture>>,
+    stencil_buffer: Option<Box<Texture>>,
+    blend_states: [BlendState; MAX_COLOR_ATTACHMENTS],
+    color_masks: [ColorWriteMask; MAX_COLOR_ATTACHMENTS],
+    viewport: Option<Viewport>,
+    scissor: Option<Rect>,
+    clear_color: Vec4,
+    clear_depth: f32,
+    clear_stencil: u8,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FrameBuffer {
@@ -23,55 +78,80 @@ impl FrameBuffer {
         Self {
             width: 0,
             height: 0,
-            color_buffer: None,
+            color_buffers: Default::default(),
             depth_buffer: None,
+            stencil_buffer: None,
+            blend_states: [BlendState::default(); MAX_COLOR_ATTACHMENTS],
+            color_masks: [ColorWriteMask::default(); MAX_COLOR_ATTACHMENTS],
+            viewport: None,
+            scissor: None,
+            clear_color: vec4_zero!(),
+            clear_depth: 1.,
+            clear_stencil: 0,
         }
     }
 
-    pub fn attach_texture(&mut self, attachment: AttachmentType, texture: Option<Box<Texture>>) {
-        let format = texture.as_ref().unwrap().get_texture_format();
-        let mut result = false;
-        if let Some(texture) = texture {
-            match attachment {
-                AttachmentType::Color => {
-                    if format == TextureFormat::SRGB8_A8 || format == TextureFormat::RGBA8 {
-                        self.color_buffer = Some(texture);
-                        result = true;
-                    }
-                },
-                AttachmentType::Depth => {
-                    if format == TextureFormat::DEPTH_FLOAT {
-                        self.depth_buffer = Some(texture);
-                        result = true;
-                    }
-                },
+    /*
+     * Attach a texture to the framebuffer, or detach the current one with
+     * None. Color attachments accept the color renderable formats, including
+     * float formats for HDR rendering, the depth attachment DEPTH_FLOAT and
+     * the stencil attachment STENCIL8. The size of
+     * the framebuffer is the smallest size of its attachments.
+     */
+    pub fn attach_texture(&mut self, attachment: AttachmentType, texture: Option<Box<Texture>>) -> Result<(), RenderError> {
+        let slot = self.slot_mut(attachment).ok_or(RenderError::InvalidAttachment(attachment))?;
+        if let Some(ref texture) = texture {
+            let format = texture.get_texture_format();
+            let accepted = match attachment {
+                AttachmentType::Color(_) => format.is_color_renderable(),
+                AttachmentType::Depth => format == TextureFormat::DEPTH_FLOAT,
+                AttachmentType::Stencil => format == TextureFormat::STENCIL8,
+            };
+            if !accepted {
+                return Err(RenderError::FormatMismatch { attachment, format });
             }
+        }
+        *slot = texture;
+        self.update_size();
+        Ok(())
+    }
+
+    /*
+     * Take the texture out of an attachment, e.g. to sample a face of a cube
+     * map after rendering to it. None if nothing is attached.
+     */
+    pub fn detach_texture(&mut self, attachment: AttachmentType) -> Option<Box<Texture>> {
+        let texture = self.slot_mut(attachment)?.take();
+        self.update_size();
+        texture
+    }
+
+    fn update_size(&mut self) {
+        if self.check_status() == FramebufferStatus::MissingAttachment {
+            self.width = 0;
+            self.height = 0;
         } else {
-            match attachment {
-                AttachmentType::Color => {
-                    if format == TextureFormat::SRGB8_A8 || format == TextureFormat::RGBA8 {
-                        self.color_buffer = None;
-                        result = true;
-                    }
-                },
-                AttachmentType::Depth => {
-                    if format == TextureFormat::DEPTH_FLOAT {
-                        self.depth_buffer = None;
-                        result = true;
-                    }
-                },
-            }
+            self.width = u32::MAX;
+            self.height = u32::MAX;
+
+            self.shrink();
         }
-        if result {
-            if self.color_buffer.is_none() && self.depth_buffer.is_none() {
-                self.width = 0;
-                self.height = 0;
-            } else {
-                self.width = std::u32::MAX;
-                self.height = std::u32::MIN;
-
-                self.shrink();
-            }
+    }
+
+    // Whether the framebuffer can be rendered to, like glCheckFramebufferStatus: complete if anything is attached.
+    pub fn check_status(&self) -> FramebufferStatus {
+        if self.color_buffers.iter().all(|buffer| buffer.is_none())
+            && self.depth_buffer.is_none() && self.stencil_buffer.is_none() {
+            return FramebufferStatus::MissingAttachment;
+        }
+        FramebufferStatus::Complete
+    }
+
+    // Ok if the framebuffer is complete, the reason why it isn't otherwise.
+    pub fn validate(&self) -> Result<(), RenderError> {
+        match self.check_status() {
+            FramebufferStatus::Complete => Ok(()),
+            status => Err(RenderError::IncompleteFramebuffer(status)),
         }
     }
 
@@ -83,59 +163,203 @@ impl FrameBuffer {
         self.height
     }
 
-    pub fn get_attachment(&self, attachment: AttachmentType) -> &Option<Box<Texture>> {
-        let result = match attachment {
-            AttachmentType::Color => {
-                &self.color_buffer
+    // The texture of an attachment, None if nothing is attached or the attachment doesn't exist.
+    pub fn get_attachment(&self, attachment: AttachmentType) -> Option<&Texture> {
+        match attachment {
+            AttachmentType::Color(index) => {
+                self.color_buffers.get(index)?.as_deref()
             },
             AttachmentType::Depth => {
-                &self.depth_buffer
+                self.depth_buffer.as_deref()
             },
-        };
+            AttachmentType::Stencil => {
+                self.stencil_buffer.as_deref()
+            },
+        }
+    }
 
-        result 
+    pub fn get_attachment_mut(&mut self, attachment: AttachmentType) -> Option<&mut Texture> {
+        self.slot_mut(attachment)?.as_deref_mut()
     }
 
+    // Where the texture of an attachment is stored, None if the attachment doesn't exist.
+    fn slot_mut(&mut self, attachment: AttachmentType) -> Option<&mut Option<Box<Texture>>> {
+        match attachment {
+            AttachmentType::Color(index) => {
+                self.color_buffers.get_mut(index)
+            },
+            AttachmentType::Depth => {
+                Some(&mut self.depth_buffer)
+            },
+            AttachmentType::Stencil => {
+                Some(&mut self.stencil_buffer)
+            },
+        }
+    }
+
+    pub fn get_blend_state(&self, index: usize) -> Option<&BlendState> {
+        self.blend_states.get(index)
+    }
+
+    // Set the blend state of every color attachment.
+    pub fn set_blend_state(&mut self, state: BlendState) {
+        self.blend_states = [state; MAX_COLOR_ATTACHMENTS];
+    }
+
+    // Set the blend state of the color attachment Color(index).
+    pub fn set_blend_state_indexed(&mut self, index: usize, state: BlendState) -> Result<(), RenderError> {
+        let slot = self.blend_states.get_mut(index).ok_or(RenderError::InvalidAttachment(AttachmentType::Color(index)))?;
+        *slot = state;
+        Ok(())
+    }
+
+    pub fn get_color_mask(&self, index: usize) -> Option<ColorWriteMask> {
+        self.color_masks.get(index).copied()
+    }
+
+    // Set the write mask of every color attachment.
+    pub fn set_color_mask(&mut self, mask: ColorWriteMask) {
+        self.color_masks = [mask; MAX_COLOR_ATTACHMENTS];
+    }
+
+    // Set the write mask of the color attachment Color(index).
+    pub fn set_color_mask_indexed(&mut self, index: usize, mask: ColorWriteMask) -> Result<(), RenderError> {
+        let slot = self.color_masks.get_mut(index).ok_or(RenderError::InvalidAttachment(AttachmentType::Color(index)))?;
+        *slot = mask;
+        Ok(())
+    }
+
+    // The viewport, which covers the whole framebuffer with depth [0, 1] unless set.
+    pub fn get_viewport(&self) -> Viewport {
+        match self.viewport {
+            Some(viewport) => viewport,
+            None => Viewport {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+                min_depth: 0.,
+                max_depth: 1.,
+            },
+        }
+    }
+
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.viewport = viewport;
+    }
+
+    // Only the pixels inside the scissor rectangle are rasterized, if set.
+    pub fn get_scissor(&self) -> Option<Rect> {
+        self.scissor
+    }
+
+    pub fn set_scissor(&mut self, scissor: Option<Rect>) {
+        self.scissor = scissor;
+    }
+
+    pub fn get_clear_color(&self) -> Vec4 {
+        self.clear_color
+    }
+
+    // Set the color written by clear_color(), in linear space.
+    pub fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.clear_color = Vec4::new(red, green, blue, alpha);
+    }
+
+    pub fn get_clear_depth(&self) -> f32 {
+        self.clear_depth
+    }
+
+    pub fn set_clear_depth(&mut self, depth: f32) {
+        self.clear_depth = depth;
+    }
+
+    pub fn get_clear_stencil(&self) -> u8 {
+        self.clear_stencil
+    }
+
+    pub fn set_clear_stencil(&mut self, stencil: u8) {
+        self.clear_stencil = stencil;
+    }
+
+    // Clear every attachment with its clear value.
     pub fn clear(&mut self) {
-        let pixel_count: usize = (self.width * self.height).try_into().unwrap();
-        if let Some(ref mut buffer) = self.color_buffer {
-            let pixels = buffer.get_texture_pixels();
-            let mut i = 0;
-            while i < pixel_count {
-                let ii = i * 4;
-                unsafe {
-                    pixels[ii] = CLEAR_COLOR[0];
-                    pixels[ii + 1] = CLEAR_COLOR[1];
-                    pixels[ii + 2] = CLEAR_COLOR[2];
-                    pixels[ii + 3] = CLEAR_COLOR[3];
+        self.clear_color();
+        self.clear_depth();
+        self.clear_stencil();
+    }
+
+    /*
+     * Fill the color attachments with the clear color, encoded to the format
+     * of each attachment. Like the other clears, only the pixels inside the
+     * scissor rectangle are written.
+     */
+    pub fn clear_color(&mut self) {
+        let area = self.clear_area();
+        for buffer in self.color_buffers.iter_mut().flatten() {
+            let pixel_size = buffer.format.pixel_size();
+            let mut color = vec![0; pixel_size];
+            buffer.format.encode(self.clear_color, &mut color);
+            let width = buffer.width;
+            for y in area.y..area.y + area.height {
+                let row = (area.x + y * width) as usize * pixel_size;
+                let pixels = &mut buffer.pixels[row..row + area.width as usize * pixel_size];
+                for pixel in pixels.chunks_exact_mut(pixel_size) {
+                    pixel.copy_from_slice(&color);
                 }
-                i += 1;
             }
         }
+    }
+
+    pub fn clear_depth(&mut self) {
+        let area = self.clear_area();
         if let Some(ref mut buffer) = self.depth_buffer {
-            let pixels = buffer.get_texture_pixels();
-            let mut i = 0;
-            while i < pixel_count {
-                pixels[i] = 1;
-                i += 1;
+            for y in area.y..area.y + area.height {
+                for x in area.x..area.x + area.width {
+                    buffer.set_depth(x, y, self.clear_depth);
+                }
             }
         }
     }
 
-    #[inline]
-    fn shrink(&mut self) {
-        self.width = u32_min(self.width, self.color_buffer.as_ref().unwrap().width);
-        self.height = u32_min(self.height, self.color_buffer.as_ref().unwrap().height);
-        self.width = u32_min(self.width, self.depth_buffer.as_ref().unwrap().width);
-        self.height = u32_min(self.height, self.depth_buffer.as_ref().unwrap().height);
+    pub fn clear_stencil(&mut self) {
+        let area = self.clear_area();
+        if let Some(ref mut buffer) = self.stencil_buffer {
+            for y in area.y..area.y + area.height {
+                for x in area.x..area.x + area.width {
+                    buffer.set_stencil(x, y, self.clear_stencil);
+                }
+            }
+        }
+    }
+
+    // The pixels written by clears: the framebuffer clipped to the scissor rectangle.
+    fn clear_area(&self) -> Rect {
+        let mut area = Rect { x: 0, y: 0, width: self.width, height: self.height };
+        if let Some(scissor) = self.scissor {
+            let x_end = u32_min(self.width, scissor.x.saturating_add(scissor.width));
+            let y_end = u32_min(self.height, scissor.y.saturating_add(scissor.height));
+            area.x = u32_min(scissor.x, x_end);
+            area.y = u32_min(scissor.y, y_end);
+            area.width = x_end - area.x;
+            area.height = y_end - area.y;
+        }
+        area
     }
-}
 
-pub fn set_clean_color(red: f32, green: f32, blue: f32, alpha: f32) {
-    unsafe {
-        CLEAR_COLOR[0] = f32_to_u8(f32_clamp01(red));
-        CLEAR_COLOR[1] = f32_to_u8(f32_clamp01(green));
-        CLEAR_COLOR[2] = f32_to_u8(f32_clamp01(blue));
-        CLEAR_COLOR[3] = f32_to_u8(f32_clamp01(alpha));
+    #[inline]
+    fn shrink(&mut self) {
+        for buffer in self.color_buffers.iter().flatten() {
+            self.width = u32_min(self.width, buffer.width);
+            self.height = u32_min(self.height, buffer.height);
+        }
+        if let Some(ref buffer) = self.depth_buffer {
+            self.width = u32_min(self.width, buffer.width);
+            self.height = u32_min(self.height, buffer.height);
+        }
+        if let Some(ref buffer) = self.stencil_buffer {
+            self.width = u32_min(self.width, buffer.width);
+            self.height = u32_min(self.height, buffer.height);
+        }
     }
 }
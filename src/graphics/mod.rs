@@ -1,9 +1,17 @@
 pub mod color;
 pub mod texture;
+pub mod sampler;
+pub mod texture_cube;
+pub mod texture_array;
+pub mod texture_3d;
 pub mod framebuffer;
 pub mod shader_context;
+pub mod shader;
+pub mod render_state;
 pub mod rasterizer;
+pub mod clipping;
 
 
 #[cfg(test)]
+#[allow(clippy::identity_op)]
 mod test;
@@ -0,0 +1,402 @@
+use crate::math::preclude::*;
+use crate::vec4_zero;
+
+use super::clipping::{clip_triangle, is_triangle_inside, ClipVertex};
+use super::framebuffer::{AttachmentType, FrameBuffer, MAX_COLOR_ATTACHMENTS};
+use super::render_state::{ColorWriteMask, DepthBias, RenderState, StencilOp};
+use super::shader::Shader;
+use super::shader_context::ShaderContext;
+use super::texture::Texture;
+
+/*
+ * Vertices are snapped to a fixed-point grid with 8 bits of sub-pixel
+ * precision, so the edge functions can be evaluated exactly with integers.
+ */
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_STEP: i64 = 1 << SUBPIXEL_BITS;
+const HALF_SUBPIXEL_STEP: i64 = SUBPIXEL_STEP / 2;
+
+/*
+ * Edge functions are evaluated in i128. Clamping the snapped coordinates to
+ * 2^60 keeps their products in range, and only affects vertices that are
+ * astronomically far away.
+ */
+const GUARD_BAND: i64 = 1 << 60;
+
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+impl Point {
+    #[inline]
+    fn snap(position: Vec3) -> Self {
+        Self {
+            x: ((position.x * SUBPIXEL_STEP as f32).round() as i64).clamp(-GUARD_BAND, GUARD_BAND),
+            y: ((position.y * SUBPIXEL_STEP as f32).round() as i64).clamp(-GUARD_BAND, GUARD_BAND),
+        }
+    }
+}
+
+// Twice the signed area of triangle (a, b, p), positive if p is on the left of a->b.
+#[inline]
+fn edge_function(a: Point, b: Point, p: Point) -> i128 {
+    let (ab_x, ab_y) = ((b.x - a.x) as i128, (b.y - a.y) as i128);
+    let (ap_x, ap_y) = ((p.x - a.x) as i128, (p.y - a.y) as i128);
+    ab_x * ap_y - ab_y * ap_x
+}
+
+/*
+ * Top-left fill rule for counter-clockwise triangles in a framebuffer whose
+ * origin is the bottom-left corner. A top edge is horizontal and runs to the
+ * left, a left edge runs downwards. Pixel centers lying exactly on any other
+ * edge are not covered, so two triangles sharing an edge never both cover
+ * the same pixel.
+ */
+#[inline]
+fn is_top_left(a: Point, b: Point) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    (dy == 0 && dx < 0) || dy < 0
+}
+
+#[inline]
+fn edge_bias(a: Point, b: Point) -> i128 {
+    match is_top_left(a, b) {
+        true => 0,
+        false => -1,
+    }
+}
+
+// Write a color to the color attachment, skipping the channels disabled by `mask`.
+#[inline]
+fn write_color(texture: &mut Texture, x: u32, y: u32, color: Vec4, mask: ColorWriteMask) {
+    let color = match mask.as_array() {
+        [true, true, true, true] => color,
+        [red, green, blue, alpha] => {
+            let stored = texture.texel_fetch(x, y, 0);
+            Vec4::new(
+                if red { color.x } else { stored.x },
+                if green { color.y } else { stored.y },
+                if blue { color.z } else { stored.z },
+                if alpha { color.w } else { stored.w },
+            )
+        },
+    };
+    texture.texel_store(x, y, 0, color);
+}
+
+// A pixel covered by a triangle, handed to the fragment callback of rasterize_triangle.
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment {
+    pub x: u32,
+    pub y: u32,
+    // Barycentric weights of the pixel center relative to the three vertices.
+    pub weights: Vec3,
+    pub front_facing: bool,
+    /*
+     * Weights of the 2x2 quad of pixels containing the fragment, in the order
+     * (x0, y0), (x0 + 1, y0), (x0, y0 + 1), (x0 + 1, y0 + 1) with x0 and y0
+     * even. Pixels of the quad outside the triangle are extrapolated, so the
+     * differences give derivatives along x and y.
+     */
+    pub quad: [Vec3; 4],
+}
+
+impl Fragment {
+    // Index of the fragment in its quad.
+    #[inline]
+    pub fn quad_index(&self) -> usize {
+        (self.x & 1) as usize + ((self.y & 1) as usize) * 2
+    }
+}
+
+// Depth offset of a triangle in window coordinates, see DepthBias.
+fn depth_offset(bias: &DepthBias, screen_coords: &[Vec3; 3]) -> f32 {
+    if bias.constant == 0. && bias.slope_scaled == 0. {
+        return 0.;
+    }
+    let [a, b, c] = screen_coords;
+    let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    let dz_dx = ((b.z - a.z) * (c.y - a.y) - (c.z - a.z) * (b.y - a.y)) / area;
+    let dz_dy = ((b.x - a.x) * (c.z - a.z) - (c.x - a.x) * (b.z - a.z)) / area;
+    let max_slope = f32_max(dz_dx.abs(), dz_dy.abs());
+
+    // Unit in the last place of the float mantissa at the largest depth.
+    let max_depth = f32_max(a.z.abs(), f32_max(b.z.abs(), c.z.abs()));
+    let exponent = ((max_depth.to_bits() >> 23) & 0xff) as i32 - 127;
+    let resolution = 2f32.powi(exponent - 23);
+
+    bias.slope_scaled * max_slope + bias.constant * resolution
+}
+
+/*
+ * Rasterize a triangle whose vertices are given in screen space: x and y are
+ * pixel coordinates with the origin at the bottom-left corner, z is the
+ * window depth tested against and written to the depth attachment.
+ *
+ * Triangles are culled according to the winding of their vertices. For
+ * every covered pixel passing the stencil and depth tests, `fragment`
+ * receives the Fragment, writes the color of the attachment Color(i) to the
+ * i-th output and returns the number of outputs written, or None to discard
+ * the pixel. Degenerate triangles are skipped.
+ */
+pub fn rasterize_triangle<F>(framebuffer: &mut FrameBuffer, state: &RenderState, screen_coords: &[Vec3; 3], mut fragment: F)
+where
+    F: FnMut(&Fragment, &mut [Vec4; MAX_COLOR_ATTACHMENTS]) -> Option<usize>,
+{
+    let width = framebuffer.get_width();
+    let height = framebuffer.get_hegiht();
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let mut points = [
+        Point::snap(screen_coords[0]),
+        Point::snap(screen_coords[1]),
+        Point::snap(screen_coords[2]),
+    ];
+    let mut order = [0, 1, 2];
+    let mut area = edge_function(points[0], points[1], points[2]);
+    if area == 0 {
+        return;
+    }
+    let front_facing = state.rasterizer.is_front_facing(area as f32);
+    if state.rasterizer.is_culled(front_facing) {
+        return;
+    }
+    // Make the triangle counter-clockwise so that the interior is positive.
+    if area < 0 {
+        points.swap(1, 2);
+        order.swap(1, 2);
+        area = -area;
+    }
+
+    // Bounding box of the triangle, clamped to the framebuffer and the scissor rectangle.
+    let mut min_x = i64::MAX;
+    let mut min_y = i64::MAX;
+    let mut max_x = i64::MIN;
+    let mut max_y = i64::MIN;
+    for point in points.iter() {
+        min_x = min_x.min(point.x);
+        min_y = min_y.min(point.y);
+        max_x = max_x.max(point.x);
+        max_y = max_y.max(point.y);
+    }
+    let mut min_x = (min_x >> SUBPIXEL_BITS).max(0);
+    let mut min_y = (min_y >> SUBPIXEL_BITS).max(0);
+    let mut max_x = (max_x >> SUBPIXEL_BITS).min(width as i64 - 1);
+    let mut max_y = (max_y >> SUBPIXEL_BITS).min(height as i64 - 1);
+    if let Some(scissor) = framebuffer.get_scissor() {
+        min_x = min_x.max(scissor.x as i64);
+        min_y = min_y.max(scissor.y as i64);
+        max_x = max_x.min(scissor.x as i64 + scissor.width as i64 - 1);
+        max_y = max_y.min(scissor.y as i64 + scissor.height as i64 - 1);
+    }
+
+    let bias = [
+        edge_bias(points[1], points[2]),
+        edge_bias(points[2], points[0]),
+        edge_bias(points[0], points[1]),
+    ];
+    let depth = Vec3::new(screen_coords[0].z, screen_coords[1].z, screen_coords[2].z);
+    let depth_state = &state.depth;
+    let offset = depth_offset(&depth_state.bias, screen_coords);
+    let stencil_state = &state.stencil;
+    let stencil_face = match front_facing {
+        true => &stencil_state.front,
+        false => &stencil_state.back,
+    };
+    let viewport = framebuffer.get_viewport();
+    let min_depth = f32_min(viewport.min_depth, viewport.max_depth);
+    let max_depth = f32_max(viewport.min_depth, viewport.max_depth);
+    let mut outputs = [vec4_zero!(); MAX_COLOR_ATTACHMENTS];
+
+    // Barycentric weights of a pixel and whether the triangle covers it.
+    let coverage = |x: i64, y: i64| {
+        let center = Point {
+            x: (x << SUBPIXEL_BITS) + HALF_SUBPIXEL_STEP,
+            y: (y << SUBPIXEL_BITS) + HALF_SUBPIXEL_STEP,
+        };
+        let e0 = edge_function(points[1], points[2], center);
+        let e1 = edge_function(points[2], points[0], center);
+        let e2 = edge_function(points[0], points[1], center);
+        let covered = e0 + bias[0] >= 0 && e1 + bias[1] >= 0 && e2 + bias[2] >= 0
+            && (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y);
+
+        // Reordered back to the caller's vertex order.
+        let mut weights = [0.; 3];
+        weights[order[0]] = e0 as f32 / area as f32;
+        weights[order[1]] = e1 as f32 / area as f32;
+        weights[order[2]] = e2 as f32 / area as f32;
+        (covered, Vec3::from(&weights))
+    };
+
+    // Walk the bounding box in 2x2 quads aligned to even coordinates.
+    for quad_y in ((min_y & !1)..=max_y).step_by(2) {
+        for quad_x in ((min_x & !1)..=max_x).step_by(2) {
+            let samples = [
+                coverage(quad_x, quad_y),
+                coverage(quad_x + 1, quad_y),
+                coverage(quad_x, quad_y + 1),
+                coverage(quad_x + 1, quad_y + 1),
+            ];
+            let quad = samples.map(|(_, weights)| weights);
+            for (i, &(covered, weights)) in samples.iter().enumerate() {
+                if !covered {
+                    continue;
+                }
+
+                let x = (quad_x + (i & 1) as i64) as u32;
+                let y = (quad_y + (i >> 1) as i64) as u32;
+                let z = f32_clamp(weights.dot(depth) + offset, min_depth, max_depth);
+                let pixel = Fragment { x, y, weights, front_facing, quad };
+
+                // The fragment stage can't change the depth, so run the tests first.
+                let stencil = match (stencil_state.enabled, framebuffer.get_attachment(AttachmentType::Stencil)) {
+                    (true, Some(buffer)) => Some(buffer.get_stencil(x, y)),
+                    _ => None,
+                };
+                let stencil_passed = match stencil {
+                    Some(stored) => stencil_face.test(stored),
+                    None => true,
+                };
+                let depth_passed = stencil_passed && match framebuffer.get_attachment(AttachmentType::Depth) {
+                    Some(buffer) => depth_state.func.compare(z, buffer.get_depth(x, y)),
+                    None => true,
+                };
+                let stencil_op = match (stencil_passed, depth_passed) {
+                    (false, _) => stencil_face.fail_op,
+                    (true, false) => stencil_face.depth_fail_op,
+                    (true, true) => stencil_face.pass_op,
+                };
+
+                /*
+                 * A fragment failing a test is only shaded if the stencil has to
+                 * be updated, since discarded fragments don't update it.
+                 */
+                if !depth_passed && (stencil.is_none() || stencil_op == StencilOp::Keep) {
+                    continue;
+                }
+                let count = match fragment(&pixel, &mut outputs) {
+                    Some(count) => count.min(MAX_COLOR_ATTACHMENTS),
+                    None => continue,
+                };
+                if let Some(stored) = stencil {
+                    if let Some(buffer) = framebuffer.get_attachment_mut(AttachmentType::Stencil) {
+                        buffer.set_stencil(x, y, stencil_face.update(stencil_op, stored));
+                    }
+                }
+                if !depth_passed {
+                    continue;
+                }
+
+                for (index, &color) in outputs.iter().enumerate().take(count) {
+                    let (blend_state, mask) = match (framebuffer.get_blend_state(index), framebuffer.get_color_mask(index)) {
+                        (Some(&blend_state), Some(mask)) => (blend_state, mask),
+                        _ => continue,
+                    };
+                    if let Some(buffer) = framebuffer.get_attachment_mut(AttachmentType::Color(index)) {
+                        let color = match blend_state.enabled {
+                            true => blend_state.blend(color, buffer.texel_fetch(x, y, 0)),
+                            false => color,
+                        };
+                        write_color(buffer, x, y, color, mask);
+                    }
+                }
+                if depth_state.write_enabled {
+                    if let Some(buffer) = framebuffer.get_attachment_mut(AttachmentType::Depth) {
+                        buffer.set_depth(x, y, z);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/*
+ * Project the vertices of a clipped triangle and rasterize it, running the
+ * fragment stage of `shader` on every covered pixel. Positions are divided by
+ * w and mapped from NDC to window coordinates by the viewport.
+ */
+fn draw_clipped_triangle<S: Shader>(framebuffer: &mut FrameBuffer, state: &RenderState, shader: &S, vertices: [&ClipVertex; 3]) {
+    let viewport = framebuffer.get_viewport();
+    let (x, y) = (viewport.x as f32, viewport.y as f32);
+    let (width, height) = (viewport.width as f32, viewport.height as f32);
+    let (near, far) = (viewport.min_depth, viewport.max_depth);
+    let mut screen_coords = [Vec3::new(0., 0., 0.); 3];
+    let mut reciprocal_w = [0.; 3];
+    for i in 0..3 {
+        let position = vertices[i].position;
+        let ndc = position.into_vec3() / position.w;
+        reciprocal_w[i] = 1. / position.w;
+        screen_coords[i] = Vec3::new(
+            x + (ndc.x + 1.) * 0.5 * width,
+            y + (ndc.y + 1.) * 0.5 * height,
+            near + (far - near) * (ndc.z + 1.) * 0.5,
+        );
+    }
+    let reciprocal_w = Vec3::from(&reciprocal_w);
+    let contexts = [&vertices[0].context, &vertices[1].context, &vertices[2].context];
+
+    let mut input = ShaderContext::new();
+    rasterize_triangle(framebuffer, state, &screen_coords, |fragment, outputs| {
+        /*
+         * Attributes divided by w are linear in screen space, so interpolate
+         * with weights scaled by 1/w and normalize them again.
+         */
+        let perspective = |weights: Vec3| {
+            let scaled = weights * reciprocal_w;
+            scaled / (scaled.x + scaled.y + scaled.z)
+        };
+        let perspective_quad = fragment.quad.map(perspective);
+        let i = fragment.quad_index();
+        input.interpolate(contexts, fragment.weights, perspective_quad[i]);
+
+        // Fine derivatives: differences along the row and the column of the fragment in its quad.
+        let (row, column) = (i & 2, i & 1);
+        let weights = [
+            fragment.quad[row + 1] - fragment.quad[row],
+            fragment.quad[column + 2] - fragment.quad[column],
+        ];
+        let perspective_weights = [
+            perspective_quad[row + 1] - perspective_quad[row],
+            perspective_quad[column + 2] - perspective_quad[column],
+        ];
+        input.differentiate(contexts, weights, perspective_weights);
+        input.set_front_facing(fragment.front_facing);
+        shader.fragment_targets(&mut input, outputs)
+    });
+}
+
+/*
+ * Run `shader` over the three vertices of a triangle and rasterize it into
+ * the framebuffer. The triangle is clipped against the view frustum in clip
+ * space first. Varyings are interpolated perspective-correctly unless the
+ * vertex stage asks otherwise.
+ */
+pub fn draw_triangle<S: Shader>(framebuffer: &mut FrameBuffer, state: &RenderState, shader: &S, vertices: [&S::Vertex; 3]) {
+    let vertices = vertices.map(|vertex| {
+        let mut context = ShaderContext::new();
+        let position = shader.vertex(&mut context, vertex);
+        ClipVertex { position, context }
+    });
+
+    if is_triangle_inside([vertices[0].position, vertices[1].position, vertices[2].position]) {
+        draw_clipped_triangle(framebuffer, state, shader, [&vertices[0], &vertices[1], &vertices[2]]);
+        return;
+    }
+    // Triangulate the clipped polygon as a fan.
+    let polygon = clip_triangle(vertices);
+    for i in 2..polygon.len() {
+        draw_clipped_triangle(framebuffer, state, shader, [&polygon[0], &polygon[i - 1], &polygon[i]]);
+    }
+}
+
+// Draw a list of triangles, every three consecutive vertices form one triangle.
+pub fn draw_arrays<S: Shader>(framebuffer: &mut FrameBuffer, state: &RenderState, shader: &S, vertices: &[S::Vertex]) {
+    for triangle in vertices.chunks_exact(3) {
+        draw_triangle(framebuffer, state, shader, [&triangle[0], &triangle[1], &triangle[2]]);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::math::preclude::*;
+
+// Comparison used by the depth and stencil tests, `compare(incoming, stored)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    Greater,
+    GreaterEqual,
+    NotEqual,
+    Always,
+}
+
+impl CompareFunc {
+    #[inline]
+    pub fn compare(self, incoming: f32, stored: f32) -> bool {
+        match self {
+            CompareFunc::Never => false,
+            CompareFunc::Less => incoming < stored,
+            CompareFunc::LessEqual => incoming <= stored,
+            CompareFunc::Equal => incoming == stored,
+            CompareFunc::Greater => incoming > stored,
+            CompareFunc::GreaterEqual => incoming >= stored,
+            CompareFunc::NotEqual => incoming != stored,
+            CompareFunc::Always => true,
+        }
+    }
+}
+
+/*
+ * Depth offset added to every fragment of a triangle, like glPolygonOffset:
+ * slope_scaled * max(|dz/dx|, |dz/dy|) + constant * r, where r is the
+ * smallest difference representable by a float depth at the triangle's
+ * maximum depth.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DepthBias {
+    pub constant: f32,
+    pub slope_scaled: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthState {
+    pub func: CompareFunc,
+    // Whether fragments passing the test write their depth.
+    pub write_enabled: bool,
+    pub bias: DepthBias,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self {
+            func: CompareFunc::Less,
+            write_enabled: true,
+            bias: DepthBias::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    ConstantColor,
+    OneMinusConstantColor,
+    ConstantAlpha,
+    OneMinusConstantAlpha,
+    SrcAlphaSaturate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendEquation {
+    // src * src_factor + dst * dst_factor
+    Add,
+    // src * src_factor - dst * dst_factor
+    Subtract,
+    // dst * dst_factor - src * src_factor
+    ReverseSubtract,
+    // min(src, dst), the factors are ignored
+    Min,
+    // max(src, dst), the factors are ignored
+    Max,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlendComponent {
+    pub src_factor: BlendFactor,
+    pub dst_factor: BlendFactor,
+    pub equation: BlendEquation,
+}
+
+impl Default for BlendComponent {
+    fn default() -> Self {
+        Self {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::Zero,
+            equation: BlendEquation::Add,
+        }
+    }
+}
+
+impl BlendComponent {
+    #[inline]
+    fn apply(&self, src: f32, src_factor: f32, dst: f32, dst_factor: f32) -> f32 {
+        match self.equation {
+            BlendEquation::Add => src * src_factor + dst * dst_factor,
+            BlendEquation::Subtract => src * src_factor - dst * dst_factor,
+            BlendEquation::ReverseSubtract => dst * dst_factor - src * src_factor,
+            BlendEquation::Min => f32_min(src, dst),
+            BlendEquation::Max => f32_max(src, dst),
+        }
+    }
+}
+
+/*
+ * How fragment colors are combined with the colors already in the color
+ * attachment. Colors are blended in linear space, an SRGB8_A8 attachment is
+ * decoded before and encoded after blending.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlendState {
+    pub enabled: bool,
+    pub color: BlendComponent,
+    pub alpha: BlendComponent,
+    pub constant: Vec4,
+}
+
+impl Default for BlendState {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: BlendComponent::default(),
+            alpha: BlendComponent::default(),
+            constant: Vec4::new(0., 0., 0., 0.),
+        }
+    }
+}
+
+impl BlendState {
+    // The usual "over" operator for non-premultiplied alpha.
+    pub fn alpha_blending() -> Self {
+        let component = BlendComponent {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            equation: BlendEquation::Add,
+        };
+        Self {
+            enabled: true,
+            color: component,
+            alpha: BlendComponent { src_factor: BlendFactor::One, ..component },
+            constant: Vec4::new(0., 0., 0., 0.),
+        }
+    }
+
+    // Factor for the color channels, as (r, g, b).
+    fn color_factor(&self, factor: BlendFactor, src: Vec4, dst: Vec4) -> Vec3 {
+        let c = self.constant;
+        match factor {
+            BlendFactor::Zero => Vec3::new(0., 0., 0.),
+            BlendFactor::One => Vec3::new(1., 1., 1.),
+            BlendFactor::SrcColor => src.into_vec3(),
+            BlendFactor::OneMinusSrcColor => Vec3::new(1. - src.x, 1. - src.y, 1. - src.z),
+            BlendFactor::DstColor => dst.into_vec3(),
+            BlendFactor::OneMinusDstColor => Vec3::new(1. - dst.x, 1. - dst.y, 1. - dst.z),
+            BlendFactor::SrcAlpha => Vec3::new(src.w, src.w, src.w),
+            BlendFactor::OneMinusSrcAlpha => Vec3::new(1. - src.w, 1. - src.w, 1. - src.w),
+            BlendFactor::DstAlpha => Vec3::new(dst.w, dst.w, dst.w),
+            BlendFactor::OneMinusDstAlpha => Vec3::new(1. - dst.w, 1. - dst.w, 1. - dst.w),
+            BlendFactor::ConstantColor => c.into_vec3(),
+            BlendFactor::OneMinusConstantColor => Vec3::new(1. - c.x, 1. - c.y, 1. - c.z),
+            BlendFactor::ConstantAlpha => Vec3::new(c.w, c.w, c.w),
+            BlendFactor::OneMinusConstantAlpha => Vec3::new(1. - c.w, 1. - c.w, 1. - c.w),
+            BlendFactor::SrcAlphaSaturate => {
+                let f = f32_min(src.w, 1. - dst.w);
+                Vec3::new(f, f, f)
+            },
+        }
+    }
+
+    fn alpha_factor(&self, factor: BlendFactor, src: Vec4, dst: Vec4) -> f32 {
+        match factor {
+            BlendFactor::Zero => 0.,
+            BlendFactor::One | BlendFactor::SrcAlphaSaturate => 1.,
+            BlendFactor::SrcColor | BlendFactor::SrcAlpha => src.w,
+            BlendFactor::OneMinusSrcColor | BlendFactor::OneMinusSrcAlpha => 1. - src.w,
+            BlendFactor::DstColor | BlendFactor::DstAlpha => dst.w,
+            BlendFactor::OneMinusDstColor | BlendFactor::OneMinusDstAlpha => 1. - dst.w,
+            BlendFactor::ConstantColor | BlendFactor::ConstantAlpha => self.constant.w,
+            BlendFactor::OneMinusConstantColor | BlendFactor::OneMinusConstantAlpha => 1. - self.constant.w,
+        }
+    }
+
+    // Blend the fragment color `src` with the attachment color `dst`.
+    pub fn blend(&self, src: Vec4, dst: Vec4) -> Vec4 {
+        if !self.enabled {
+            return src;
+        }
+        let src_factor = self.color_factor(self.color.src_factor, src, dst);
+        let dst_factor = self.color_factor(self.color.dst_factor, src, dst);
+        let src_alpha_factor = self.alpha_factor(self.alpha.src_factor, src, dst);
+        let dst_alpha_factor = self.alpha_factor(self.alpha.dst_factor, src, dst);
+        Vec4::new(
+            self.color.apply(src.x, src_factor.x, dst.x, dst_factor.x),
+            self.color.apply(src.y, src_factor.y, dst.y, dst_factor.y),
+            self.color.apply(src.z, src_factor.z, dst.z, dst_factor.z),
+            self.alpha.apply(src.w, src_alpha_factor, dst.w, dst_alpha_factor),
+        )
+    }
+}
+
+// What happens to the stencil value of a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    IncrSat,
+    DecrSat,
+    Invert,
+    IncrWrap,
+    DecrWrap,
+}
+
+impl StencilOp {
+    #[inline]
+    pub fn apply(self, stored: u8, reference: u8) -> u8 {
+        match self {
+            StencilOp::Keep => stored,
+            StencilOp::Zero => 0,
+            StencilOp::Replace => reference,
+            StencilOp::IncrSat => stored.saturating_add(1),
+            StencilOp::DecrSat => stored.saturating_sub(1),
+            StencilOp::Invert => !stored,
+            StencilOp::IncrWrap => stored.wrapping_add(1),
+            StencilOp::DecrWrap => stored.wrapping_sub(1),
+        }
+    }
+}
+
+/*
+ * Stencil test of one facing: passes if
+ * (reference & read_mask) func (stored & read_mask). Only the bits in
+ * write_mask are changed by the operations.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StencilFaceState {
+    pub func: CompareFunc,
+    // The stencil test fails.
+    pub fail_op: StencilOp,
+    // The stencil test passes but the depth test fails.
+    pub depth_fail_op: StencilOp,
+    // Both tests pass.
+    pub pass_op: StencilOp,
+    pub reference: u8,
+    pub read_mask: u8,
+    pub write_mask: u8,
+}
+
+impl Default for StencilFaceState {
+    fn default() -> Self {
+        Self {
+            func: CompareFunc::Always,
+            fail_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            pass_op: StencilOp::Keep,
+            reference: 0,
+            read_mask: 0xff,
+            write_mask: 0xff,
+        }
+    }
+}
+
+impl StencilFaceState {
+    #[inline]
+    pub fn test(&self, stored: u8) -> bool {
+        let reference = self.reference & self.read_mask;
+        let stored = stored & self.read_mask;
+        self.func.compare(reference as f32, stored as f32)
+    }
+
+    // The new stencil value after `op`, honoring the write mask.
+    #[inline]
+    pub fn update(&self, op: StencilOp, stored: u8) -> u8 {
+        let value = op.apply(stored, self.reference);
+        (stored & !self.write_mask) | (value & self.write_mask)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StencilState {
+    pub enabled: bool,
+    pub front: StencilFaceState,
+    pub back: StencilFaceState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+}
+
+// Winding order of front-facing triangles in screen space (origin at the bottom-left corner).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontFace {
+    CounterClockwise,
+    Clockwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterizerState {
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+}
+
+impl Default for RasterizerState {
+    fn default() -> Self {
+        Self {
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+        }
+    }
+}
+
+impl RasterizerState {
+    // Whether a triangle with the signed screen-space area is front-facing.
+    #[inline]
+    pub fn is_front_facing(&self, area: f32) -> bool {
+        match self.front_face {
+            FrontFace::CounterClockwise => area > 0.,
+            FrontFace::Clockwise => area < 0.,
+        }
+    }
+
+    #[inline]
+    pub fn is_culled(&self, front_facing: bool) -> bool {
+        match self.cull_mode {
+            CullMode::None => false,
+            CullMode::Front => front_facing,
+            CullMode::Back => !front_facing,
+        }
+    }
+}
+
+// Which channels of a color attachment are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorWriteMask {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl Default for ColorWriteMask {
+    fn default() -> Self {
+        Self {
+            red: true,
+            green: true,
+            blue: true,
+            alpha: true,
+        }
+    }
+}
+
+impl ColorWriteMask {
+    #[inline]
+    pub fn as_array(&self) -> [bool; 4] {
+        [self.red, self.green, self.blue, self.alpha]
+    }
+}
+
+// Fixed-function state used by the pipeline while drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RenderState {
+    pub rasterizer: RasterizerState,
+    pub depth: DepthState,
+    pub stencil: StencilState,
+}
@@ -0,0 +1,317 @@
+use crate::math::preclude::{Vec2, Vec4, f32_max, f32_min};
+use crate::vec4_zero;
+
+use super::render_state::CompareFunc;
+use super::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    // The texel whose center is the closest.
+    Nearest,
+    // Bilinear interpolation of the four closest texels.
+    Linear,
+}
+
+// How mip levels are selected when the texture is minified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipmapFilter {
+    // Only the base level is sampled.
+    None,
+    // The closest level.
+    Nearest,
+    // Linear interpolation between the two closest levels, trilinear filtering with Filter::Linear.
+    Linear,
+}
+
+// Texels averaged by Sampler::sample_compare(), each tap is filtered with the mag filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PcfKernel {
+    // A single tap, bilinear PCF with Filter::Linear.
+    Bilinear,
+    // A grid of taps one texel apart.
+    Box3x3,
+    Box5x5,
+    // 16 taps on a Poisson disk of `radius` texels.
+    Poisson { radius: f32 },
+}
+
+// Offsets of the taps of PcfKernel::Poisson on the unit disk.
+const POISSON_DISK: [(f32, f32); 16] = [
+    (-0.9420162, -0.3990622), (0.9455861, -0.7689073), (-0.0941841, -0.9293887), (0.3449594, 0.2938776),
+    (-0.9158858, 0.4577143), (-0.8154423, -0.8791246), (-0.3827754, 0.2767684), (0.974844, 0.7564838),
+    (0.4432333, -0.9751155), (0.5374298, -0.4737342), (-0.2649691, -0.4189302), (0.7919751, 0.1909019),
+    (-0.2418884, 0.9970651), (-0.8140996, 0.9143759), (0.1998413, 0.7864137), (0.1438316, -0.1410079),
+];
+
+// How texture coordinates outside [0, 1] are mapped to texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    // Texels outside the texture take the border color of the sampler.
+    ClampToBorder,
+}
+
+impl WrapMode {
+    // Map the texel index `index` to [0, size), None if it is a border texel.
+    #[inline]
+    pub(crate) fn apply(&self, index: i64, size: u32) -> Option<u32> {
+        let size = size as i64;
+        let index = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::MirroredRepeat => {
+                let index = index.rem_euclid(2 * size);
+                match index < size {
+                    true => index,
+                    false => 2 * size - 1 - index,
+                }
+            },
+            WrapMode::ClampToEdge => index.clamp(0, size - 1),
+            WrapMode::ClampToBorder => {
+                if index < 0 || index >= size {
+                    return None;
+                }
+                index
+            },
+        };
+        Some(index as u32)
+    }
+}
+
+/*
+ * Sampling state used to read textures from shaders. Texture coordinates
+ * follow the texel-center convention: texel i of a texture of size n covers
+ * [i/n, (i+1)/n] and its center is at (i+0.5)/n.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmap_filter: MipmapFilter,
+    // Bias added to the level of detail before selecting mip levels.
+    pub lod_bias: f32,
+    /*
+     * Maximum number of probes taken along the major axis of the pixel
+     * footprint by sample_grad(), 1 disables anisotropic filtering.
+     */
+    pub max_anisotropy: f32,
+    // Wrap modes along u, v and w, the last one only applies to 3D textures.
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+    pub wrap_r: WrapMode,
+    pub border_color: Vec4,
+    /*
+     * Comparison of sample_compare(), `compare(reference, depth)`. The default
+     * LessEqual passes where the reference is not behind the stored depth.
+     */
+    pub compare: CompareFunc,
+    pub pcf_kernel: PcfKernel,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            mipmap_filter: MipmapFilter::None,
+            lod_bias: 0.,
+            max_anisotropy: 1.,
+            wrap_s: WrapMode::ClampToEdge,
+            wrap_t: WrapMode::ClampToEdge,
+            wrap_r: WrapMode::ClampToEdge,
+            border_color: vec4_zero!(),
+            compare: CompareFunc::LessEqual,
+            pcf_kernel: PcfKernel::Bilinear,
+        }
+    }
+}
+
+impl Sampler {
+    pub fn new(filter: Filter, wrap: WrapMode) -> Self {
+        Self {
+            min_filter: filter,
+            mag_filter: filter,
+            wrap_s: wrap,
+            wrap_t: wrap,
+            wrap_r: wrap,
+            ..Default::default()
+        }
+    }
+
+    /*
+     * Sample `texture` at `texcoord`. Without screen-space derivatives the
+     * texture is considered magnified, so the base level and the mag filter
+     * are used.
+     */
+    pub fn sample(&self, texture: &Texture, texcoord: Vec2) -> Vec4 {
+        self.sample_lod(texture, texcoord, 0.)
+    }
+
+    /*
+     * Sample `texture` at an explicit level of detail, the log2 of the number
+     * of texels per pixel. A positive LOD minifies the texture and selects the
+     * mip levels according to the mipmap filter.
+     */
+    pub fn sample_lod(&self, texture: &Texture, texcoord: Vec2, lod: f32) -> Vec4 {
+        self.filter_levels(texture.get_level_count(), lod, |level, filter| {
+            self.filter(texture.get_level(level), texcoord, filter)
+        })
+    }
+
+    /*
+     * Select the mip levels of a texture with `level_count` levels at `lod`
+     * and blend them, `filter_level` filters one level with the given filter.
+     * Shared by all the texture types.
+     */
+    pub(crate) fn filter_levels<F>(&self, level_count: usize, lod: f32, filter_level: F) -> Vec4
+    where
+        F: Fn(usize, Filter) -> Vec4,
+    {
+        let lod = lod + self.lod_bias;
+        // A NaN LOD, e.g. from degenerate derivatives, is treated as 0.
+        if lod.is_nan() || lod <= 0. {
+            return filter_level(0, self.mag_filter);
+        }
+        let max_level = (level_count - 1) as f32;
+        let lod = f32_min(lod, max_level);
+        match self.mipmap_filter {
+            MipmapFilter::None => filter_level(0, self.min_filter),
+            MipmapFilter::Nearest => filter_level(lod.round() as usize, self.min_filter),
+            MipmapFilter::Linear => {
+                let level = lod.floor();
+                let t = lod - level;
+                let lower = filter_level(level as usize, self.min_filter);
+                if t == 0. {
+                    return lower;
+                }
+                let upper = filter_level(usize::min(level as usize + 1, level_count - 1), self.min_filter);
+                lower * (1. - t) + upper * t
+            },
+        }
+    }
+
+    /*
+     * Sample `texture` with the screen-space derivatives of `texcoord`, as
+     * given by ShaderContext::get_vec2_derivatives(). The LOD is computed from
+     * the longer side of the pixel footprint in texel space.
+     *
+     * With anisotropic filtering, an elongated footprint is covered by up to
+     * max_anisotropy probes spread along its major axis, each sampled at the
+     * LOD of the minor axis.
+     */
+    pub fn sample_grad(&self, texture: &Texture, texcoord: Vec2, ddx: Vec2, ddy: Vec2) -> Vec4 {
+        let size = Vec2::new(texture.width as f32, texture.height as f32);
+        let x_length = (ddx * size).magnitude();
+        let y_length = (ddy * size).magnitude();
+        let (major_axis, major, minor) = match x_length >= y_length {
+            true => (ddx, x_length, y_length),
+            false => (ddy, y_length, x_length),
+        };
+        let ratio = match minor > 0. {
+            true => major / minor,
+            false => f32::INFINITY,
+        };
+        let probes = f32_min(ratio, self.max_anisotropy.floor()).ceil();
+        if probes <= 1. || major == 0. {
+            return self.sample_lod(texture, texcoord, Self::compute_lod(texture, ddx, ddy));
+        }
+
+        let lod = (major / probes).log2();
+        let mut color = vec4_zero!();
+        for i in 0..probes as u32 {
+            let offset = (i as f32 + 0.5) / probes - 0.5;
+            color = color + self.sample_lod(texture, texcoord + major_axis * offset, lod);
+        }
+        color / probes
+    }
+
+    // Level of detail of a pixel footprint whose sides are ddx and ddy in texture coordinates.
+    pub fn compute_lod(texture: &Texture, ddx: Vec2, ddy: Vec2) -> f32 {
+        let size = Vec2::new(texture.width as f32, texture.height as f32);
+        let ddx = ddx * size;
+        let ddy = ddy * size;
+        let rho = f32_max(ddx.dot(ddx), ddy.dot(ddy));
+        // log2(sqrt(rho)), rho of zero gives -inf and magnifies.
+        0.5 * rho.log2()
+    }
+
+    /*
+     * Shadow lookup in a depth texture: the fraction of the texels around
+     * `texcoord` whose depth passes `compare(reference, depth)`, averaged over
+     * the PCF kernel. Only the base level is used, texels outside the texture
+     * compare against the red channel of the border color.
+     */
+    pub fn sample_compare(&self, texture: &Texture, texcoord: Vec2, reference: f32) -> f32 {
+        let texel = Vec2::new(1. / texture.width as f32, 1. / texture.height as f32);
+        let tap = |x: f32, y: f32| self.compare_tap(texture, texcoord + Vec2::new(x, y) * texel, reference);
+        let grid = |radius: i32| {
+            let mut sum = 0.;
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    sum += tap(x as f32, y as f32);
+                }
+            }
+            let width = (2 * radius + 1) as f32;
+            sum / (width * width)
+        };
+        match self.pcf_kernel {
+            PcfKernel::Bilinear => tap(0., 0.),
+            PcfKernel::Box3x3 => grid(1),
+            PcfKernel::Box5x5 => grid(2),
+            PcfKernel::Poisson { radius } => {
+                let sum: f32 = POISSON_DISK.iter().map(|&(x, y)| tap(x * radius, y * radius)).sum();
+                sum / POISSON_DISK.len() as f32
+            },
+        }
+    }
+
+    // Compare the texels of one tap, weighting the results bilinearly with Filter::Linear.
+    fn compare_tap(&self, texture: &Texture, texcoord: Vec2, reference: f32) -> f32 {
+        let passed = |x: i64, y: i64| match self.compare.compare(reference, self.fetch(texture, x, y).x) {
+            true => 1.,
+            false => 0.,
+        };
+        let x = texcoord.x * texture.width as f32;
+        let y = texcoord.y * texture.height as f32;
+        match self.mag_filter {
+            Filter::Nearest => passed(x.floor() as i64, y.floor() as i64),
+            Filter::Linear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let bottom = passed(x0, y0) * (1. - tx) + passed(x0 + 1, y0) * tx;
+                let top = passed(x0, y0 + 1) * (1. - tx) + passed(x0 + 1, y0 + 1) * tx;
+                bottom * (1. - ty) + top * ty
+            },
+        }
+    }
+
+    #[inline]
+    fn fetch(&self, texture: &Texture, x: i64, y: i64) -> Vec4 {
+        match (self.wrap_s.apply(x, texture.width), self.wrap_t.apply(y, texture.height)) {
+            (Some(x), Some(y)) => texture.texel_fetch(x, y, 0),
+            _ => self.border_color,
+        }
+    }
+
+    // Filter the texels of one level around `texcoord`.
+    pub(crate) fn filter(&self, texture: &Texture, texcoord: Vec2, filter: Filter) -> Vec4 {
+        let x = texcoord.x * texture.width as f32;
+        let y = texcoord.y * texture.height as f32;
+        match filter {
+            Filter::Nearest => self.fetch(texture, x.floor() as i64, y.floor() as i64),
+            Filter::Linear => {
+                // Offset by half a texel so the weights are relative to texel centers.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let bottom = self.fetch(texture, x0, y0) * (1. - tx) + self.fetch(texture, x0 + 1, y0) * tx;
+                let top = self.fetch(texture, x0, y0 + 1) * (1. - tx) + self.fetch(texture, x0 + 1, y0 + 1) * tx;
+                bottom * (1. - ty) + top * ty
+            },
+        }
+    }
+}
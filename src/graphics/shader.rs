@@ -0,0 +1,37 @@
+use crate::math::preclude::Vec4;
+
+use super::framebuffer::MAX_COLOR_ATTACHMENTS;
+use super::shader_context::ShaderContext;
+
+/*
+ * A programmable shader. Uniform values are the fields of the type
+ * implementing this trait.
+ */
+pub trait Shader {
+    // Per-vertex input data.
+    type Vertex;
+
+    /*
+     * Vertex stage: write the varyings into `output` and return the
+     * clip-space position of the vertex.
+     */
+    fn vertex(&self, output: &mut ShaderContext, vertex: &Self::Vertex) -> Vec4;
+
+    /*
+     * Fragment stage: read the interpolated varyings from `input` and return
+     * the fragment color, or None to discard the fragment.
+     */
+    fn fragment(&self, input: &mut ShaderContext) -> Option<Vec4>;
+
+    /*
+     * Fragment stage for multiple render targets: write the color of the
+     * attachment Color(i) to outputs[i] and return the number of outputs
+     * written, or None to discard the fragment. Attachments without an output
+     * are left untouched. By default the result of fragment() goes to
+     * Color(0).
+     */
+    fn fragment_targets(&self, input: &mut ShaderContext, outputs: &mut [Vec4; MAX_COLOR_ATTACHMENTS]) -> Option<usize> {
+        outputs[0] = self.fragment(input)?;
+        Some(1)
+    }
+}
@@ -1,65 +1,204 @@
+use std::ops;
+
 use crate::math::preclude::{Vec2, Vec3, Vec4};
 use crate::{vec2_zero, vec3_zero, vec4_zero};
 
-const MAX_F32_VARIABLES: usize = 2;
-const MAX_VEC2_VARIABLES: usize = 2;
-const MAX_VEC3_VARIABLES: usize = 2;
-const MAX_VEC4_VARIABLES: usize = 2;
+// Number of locations reserved for each type of variable by ShaderContext::new().
+const DEFAULT_CAPACITY: usize = 4;
+
+// How a variable is interpolated across a triangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    // Perspective-correct interpolation, weighted by 1/w.
+    Smooth,
+    // Linear interpolation in screen space.
+    NoPerspective,
+    // The value of the first vertex is used for the whole triangle.
+    Flat,
+}
+
+// A live variable of a context: its type and location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Varying {
+    F32(usize),
+    Vec2(usize),
+    Vec3(usize),
+    Vec4(usize),
+}
+
+trait Variable: Copy + ops::Add<Output = Self> + ops::Mul<f32, Output = Self> {
+    const ZERO: Self;
+}
+
+impl Variable for f32 {
+    const ZERO: Self = 0.;
+}
+
+impl Variable for Vec2 {
+    const ZERO: Self = vec2_zero!();
+}
+
+impl Variable for Vec3 {
+    const ZERO: Self = vec3_zero!();
+}
+
+impl Variable for Vec4 {
+    const ZERO: Self = vec4_zero!();
+}
+
+// Storage of one type of variables, indexed by location.
+#[derive(Debug, Clone)]
+struct Variables<T> {
+    /* Array to store the variables. */
+    values: Vec<T>,
+
+    /* Record whether the variable at each location is used. */
+    allocs: Vec<bool>,
+
+    /* Interpolation qualifier of the variable at each location. */
+    modes: Vec<Interpolation>,
+
+    /* Queue of locations of the variables that have been used. */
+    idx_queue: Vec<usize>,
+
+    /* Screen-space derivatives along x and y of the interpolated variables. */
+    ddx: Vec<T>,
+    ddy: Vec<T>,
+}
+
+impl<T: Variable> Variables<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: vec![T::ZERO; capacity],
+            allocs: vec![false; capacity],
+            modes: vec![Interpolation::Smooth; capacity],
+            idx_queue: Vec::with_capacity(capacity),
+            ddx: vec![T::ZERO; capacity],
+            ddy: vec![T::ZERO; capacity],
+        }
+    }
+
+    #[inline]
+    fn is_allocated(&self, location: usize) -> bool {
+        location < self.allocs.len() && self.allocs[location]
+    }
+
+    fn alloc(&mut self, location: usize) -> &mut T {
+        if location >= self.values.len() {
+            self.values.resize(location + 1, T::ZERO);
+            self.allocs.resize(location + 1, false);
+            self.modes.resize(location + 1, Interpolation::Smooth);
+            self.ddx.resize(location + 1, T::ZERO);
+            self.ddy.resize(location + 1, T::ZERO);
+        }
+        if !self.allocs[location] {
+            self.allocs[location] = true;
+            self.idx_queue.push(location);
+        }
+        &mut self.values[location]
+    }
+
+    fn get(&mut self, location: usize) -> &mut T {
+        if !self.is_allocated(location) {
+            panic!("The variable at location {} is not allocated.", location);
+        }
+        &mut self.values[location]
+    }
+
+    // Value of a variable, zero if it is not allocated.
+    #[inline]
+    fn value(&self, location: usize) -> T {
+        match self.is_allocated(location) {
+            true => self.values[location],
+            false => T::ZERO,
+        }
+    }
 
-#[derive(Debug)]
+    fn clear(&mut self) {
+        for location in self.idx_queue.drain(..) {
+            self.allocs[location] = false;
+            self.modes[location] = Interpolation::Smooth;
+            self.ddx[location] = T::ZERO;
+            self.ddy[location] = T::ZERO;
+        }
+    }
+
+    fn interpolate(&mut self, sources: [&Self; 3], weights: Vec3, perspective_weights: Vec3) {
+        let [s0, s1, s2] = sources;
+        self.clear();
+        for &location in s0.idx_queue.iter() {
+            let mode = s0.modes[location];
+            let w = match mode {
+                Interpolation::Smooth => perspective_weights,
+                Interpolation::NoPerspective => weights,
+                Interpolation::Flat => Vec3::new(1., 0., 0.),
+            };
+            *self.alloc(location) = s0.value(location) * w.x + s1.value(location) * w.y + s2.value(location) * w.z;
+            self.modes[location] = mode;
+        }
+    }
+
+    fn derivatives(&self, location: usize) -> (T, T) {
+        if !self.is_allocated(location) {
+            panic!("The variable at location {} is not allocated.", location);
+        }
+        (self.ddx[location], self.ddy[location])
+    }
+
+    // The derivatives of an interpolated value are the interpolation of the sources with the derivatives of the weights.
+    fn differentiate(&mut self, sources: [&Self; 3], weights: [Vec3; 2], perspective_weights: [Vec3; 2]) {
+        let [s0, s1, s2] = sources;
+        for &location in self.idx_queue.iter() {
+            let [dx, dy] = match self.modes[location] {
+                Interpolation::Smooth => perspective_weights,
+                Interpolation::NoPerspective => weights,
+                Interpolation::Flat => [vec3_zero!(); 2],
+            };
+            let (v0, v1, v2) = (s0.value(location), s1.value(location), s2.value(location));
+            self.ddx[location] = v0 * dx.x + v1 * dx.y + v2 * dx.z;
+            self.ddy[location] = v0 * dy.x + v1 * dy.y + v2 * dy.z;
+        }
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mut result = self.clone();
+        for &location in self.idx_queue.iter() {
+            if self.modes[location] != Interpolation::Flat {
+                result.values[location] = self.values[location] * (1. - t) + other.value(location) * t;
+            }
+        }
+        result
+    }
+
+    fn copy_flat(&mut self, source: &Self) {
+        for &location in self.idx_queue.iter() {
+            if self.modes[location] == Interpolation::Flat {
+                self.values[location] = source.value(location);
+            }
+        }
+    }
+}
+
+/*
+ * Variables passed from the vertex stage to the fragment stage. A variable
+ * is allocated at a location by the vertex stage with one of the alloc_*
+ * functions, the pipeline then interpolates exactly the allocated variables
+ * and hands them to the fragment stage, which reads them with get_*.
+ */
+#[derive(Debug, Clone)]
 pub struct ShaderContext {
-    /* Array to store various types of variables. */
-    f32_vars: [f32; MAX_F32_VARIABLES],
-    vec2_vars: [Vec2; MAX_VEC2_VARIABLES],
-    vec3_vars: [Vec3; MAX_VEC3_VARIABLES],
-    vec4_vars: [Vec4; MAX_VEC4_VARIABLES],
-
-    /* Record whether the variable at each index is used. */
-    f32_allocs: [bool; MAX_F32_VARIABLES],
-    vec2_allocs: [bool; MAX_VEC2_VARIABLES],
-    vec3_allocs: [bool; MAX_VEC3_VARIABLES],
-    vec4_allocs: [bool; MAX_VEC4_VARIABLES],
-
-    /* Queue of indices of various that have been used. */
-    f32_idx_queue: [i8; MAX_F32_VARIABLES],
-    vec2_idx_queue: [i8; MAX_VEC2_VARIABLES],
-    vec3_idx_queue: [i8; MAX_VEC3_VARIABLES],
-    vec4_idx_queue: [i8; MAX_VEC4_VARIABLES],
-
-    /* Number of variables used. */
-    f32_var_count: u8,
-    vec2_var_count: u8,
-    vec3_var_count: u8,
-    vec4_var_count: u8,
+    f32_vars: Variables<f32>,
+    vec2_vars: Variables<Vec2>,
+    vec3_vars: Variables<Vec3>,
+    vec4_vars: Variables<Vec4>,
+
+    /* Whether the fragment belongs to a front-facing triangle. */
+    front_facing: bool,
 }
 
 impl Default for ShaderContext {
     fn default() -> Self {
-        Self {
-        /* Array to store various types of variables. */
-        f32_vars: [0.; MAX_F32_VARIABLES],
-        vec2_vars: [vec2_zero!(); MAX_VEC2_VARIABLES],
-        vec3_vars: [vec3_zero!(); MAX_VEC3_VARIABLES],
-        vec4_vars: [vec4_zero!(); MAX_VEC4_VARIABLES],
-
-        /* Record whether the variable at each index is used. */
-        f32_allocs: [false; MAX_F32_VARIABLES],
-        vec2_allocs: [false; MAX_VEC2_VARIABLES],
-        vec3_allocs: [false; MAX_VEC3_VARIABLES],
-        vec4_allocs: [false; MAX_VEC4_VARIABLES],
-
-        /* Queue of indices of various that have been used. */
-        f32_idx_queue: [0; MAX_F32_VARIABLES],
-        vec2_idx_queue: [0; MAX_VEC2_VARIABLES],
-        vec3_idx_queue: [0; MAX_VEC3_VARIABLES],
-        vec4_idx_queue: [0; MAX_VEC4_VARIABLES],
-
-        /* Number of variables used. */
-        f32_var_count: 0,
-        vec2_var_count: 0,
-        vec3_var_count: 0,
-        vec4_var_count: 0,
-        }
+        Self::with_capacity(DEFAULT_CAPACITY)
     }
 }
 
@@ -68,38 +207,170 @@ impl ShaderContext {
         Self::default()
     }
 
-    pub fn clear(&mut self) {
-        for ref mut i in self.f32_allocs {
-            *i = false;
-        }
-        for ref mut i in self.vec2_allocs {
-            *i = false;
-        }
-        for ref mut i in self.vec3_allocs {
-            *i = false;
-        }
-        for ref mut i in self.vec4_allocs {
-            *i = false;
+    /*
+     * Create a context with `capacity` locations reserved for each type of
+     * variable. Allocating beyond the capacity grows the storage.
+     */
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            f32_vars: Variables::with_capacity(capacity),
+            vec2_vars: Variables::with_capacity(capacity),
+            vec3_vars: Variables::with_capacity(capacity),
+            vec4_vars: Variables::with_capacity(capacity),
+            front_facing: true,
         }
-        self.f32_var_count = 0;
-        self.vec2_var_count = 0;
-        self.vec3_var_count = 0;
-        self.vec4_var_count = 0;
     }
 
-    pub fn get_f32(&mut self, index: usize) -> &mut f32 {
-        &mut self.f32_vars[index]
+    // Free all the variables and reset their interpolation qualifiers.
+    pub fn clear(&mut self) {
+        self.f32_vars.clear();
+        self.vec2_vars.clear();
+        self.vec3_vars.clear();
+        self.vec4_vars.clear();
+    }
+
+    pub fn alloc_f32(&mut self, location: usize) -> &mut f32 {
+        self.f32_vars.alloc(location)
+    }
+
+    pub fn alloc_vec2(&mut self, location: usize) -> &mut Vec2 {
+        self.vec2_vars.alloc(location)
+    }
+
+    pub fn alloc_vec3(&mut self, location: usize) -> &mut Vec3 {
+        self.vec3_vars.alloc(location)
+    }
+
+    pub fn alloc_vec4(&mut self, location: usize) -> &mut Vec4 {
+        self.vec4_vars.alloc(location)
+    }
+
+    // Panics if the variable at `location` is not allocated.
+    pub fn get_f32(&mut self, location: usize) -> &mut f32 {
+        self.f32_vars.get(location)
+    }
+
+    pub fn get_vec2(&mut self, location: usize) -> &mut Vec2 {
+        self.vec2_vars.get(location)
+    }
+
+    pub fn get_vec3(&mut self, location: usize) -> &mut Vec3 {
+        self.vec3_vars.get(location)
+    }
+
+    pub fn get_vec4(&mut self, location: usize) -> &mut Vec4 {
+        self.vec4_vars.get(location)
+    }
+
+    // Set the interpolation qualifier of a variable, allocating it if needed.
+    pub fn set_f32_interpolation(&mut self, location: usize, mode: Interpolation) {
+        self.f32_vars.alloc(location);
+        self.f32_vars.modes[location] = mode;
+    }
+
+    pub fn set_vec2_interpolation(&mut self, location: usize, mode: Interpolation) {
+        self.vec2_vars.alloc(location);
+        self.vec2_vars.modes[location] = mode;
+    }
+
+    pub fn set_vec3_interpolation(&mut self, location: usize, mode: Interpolation) {
+        self.vec3_vars.alloc(location);
+        self.vec3_vars.modes[location] = mode;
+    }
+
+    pub fn set_vec4_interpolation(&mut self, location: usize, mode: Interpolation) {
+        self.vec4_vars.alloc(location);
+        self.vec4_vars.modes[location] = mode;
+    }
+
+    /*
+     * Screen-space derivatives (d/dx, d/dy) of an interpolated variable in the
+     * fragment stage. They are the differences between neighbouring pixels of
+     * the 2x2 quad the fragment belongs to. Panics if the variable at
+     * `location` is not allocated.
+     */
+    pub fn get_f32_derivatives(&self, location: usize) -> (f32, f32) {
+        self.f32_vars.derivatives(location)
+    }
+
+    pub fn get_vec2_derivatives(&self, location: usize) -> (Vec2, Vec2) {
+        self.vec2_vars.derivatives(location)
+    }
+
+    pub fn get_vec3_derivatives(&self, location: usize) -> (Vec3, Vec3) {
+        self.vec3_vars.derivatives(location)
+    }
+
+    pub fn get_vec4_derivatives(&self, location: usize) -> (Vec4, Vec4) {
+        self.vec4_vars.derivatives(location)
+    }
+
+    // Whether the fragment being shaded belongs to a front-facing triangle.
+    pub fn is_front_facing(&self) -> bool {
+        self.front_facing
+    }
+
+    pub(crate) fn set_front_facing(&mut self, front_facing: bool) {
+        self.front_facing = front_facing;
+    }
+
+    // Enumerate the allocated variables in allocation order.
+    pub fn varyings(&self) -> impl Iterator<Item = Varying> + '_ {
+        let f32s = self.f32_vars.idx_queue.iter().map(|&location| Varying::F32(location));
+        let vec2s = self.vec2_vars.idx_queue.iter().map(|&location| Varying::Vec2(location));
+        let vec3s = self.vec3_vars.idx_queue.iter().map(|&location| Varying::Vec3(location));
+        let vec4s = self.vec4_vars.idx_queue.iter().map(|&location| Varying::Vec4(location));
+        f32s.chain(vec2s).chain(vec3s).chain(vec4s)
+    }
+
+    /*
+     * Linearly interpolate the allocated variables between self and `other`,
+     * used to create the vertices introduced by clipping. Flat variables keep
+     * the value of self.
+     */
+    pub fn lerp(&self, other: &ShaderContext, t: f32) -> ShaderContext {
+        Self {
+            f32_vars: self.f32_vars.lerp(&other.f32_vars, t),
+            vec2_vars: self.vec2_vars.lerp(&other.vec2_vars, t),
+            vec3_vars: self.vec3_vars.lerp(&other.vec3_vars, t),
+            vec4_vars: self.vec4_vars.lerp(&other.vec4_vars, t),
+            front_facing: self.front_facing,
+        }
     }
 
-    pub fn get_vec2(&mut self, index: usize) -> &mut Vec2 {
-        &mut self.vec2_vars[index]
+    // Overwrite the flat variables with the values of `source`, the provoking vertex of a clipped triangle.
+    pub fn copy_flat(&mut self, source: &ShaderContext) {
+        self.f32_vars.copy_flat(&source.f32_vars);
+        self.vec2_vars.copy_flat(&source.vec2_vars);
+        self.vec3_vars.copy_flat(&source.vec3_vars);
+        self.vec4_vars.copy_flat(&source.vec4_vars);
     }
 
-    pub fn get_vec3(&mut self, index: usize) -> &mut Vec3 {
-        &mut self.vec3_vars[index]
+    /*
+     * Interpolate the variables of the three contexts of a triangle, storing
+     * the results in self. `weights` are the barycentric weights in screen
+     * space, `perspective_weights` are the same weights corrected by the 1/w
+     * of each vertex. Exactly the variables allocated in the first context
+     * are interpolated, with its interpolation qualifiers.
+     */
+    pub fn interpolate(&mut self, contexts: [&ShaderContext; 3], weights: Vec3, perspective_weights: Vec3) {
+        let [c0, c1, c2] = contexts;
+        self.f32_vars.interpolate([&c0.f32_vars, &c1.f32_vars, &c2.f32_vars], weights, perspective_weights);
+        self.vec2_vars.interpolate([&c0.vec2_vars, &c1.vec2_vars, &c2.vec2_vars], weights, perspective_weights);
+        self.vec3_vars.interpolate([&c0.vec3_vars, &c1.vec3_vars, &c2.vec3_vars], weights, perspective_weights);
+        self.vec4_vars.interpolate([&c0.vec4_vars, &c1.vec4_vars, &c2.vec4_vars], weights, perspective_weights);
     }
 
-    pub fn get_vec4(&mut self, index: usize) -> &mut Vec4 {
-        &mut self.vec4_vars[index]
+    /*
+     * Compute the screen-space derivatives of the variables interpolated by
+     * interpolate(). `weights` and `perspective_weights` hold the derivatives
+     * along x and y of the corresponding weights passed to interpolate().
+     */
+    pub fn differentiate(&mut self, contexts: [&ShaderContext; 3], weights: [Vec3; 2], perspective_weights: [Vec3; 2]) {
+        let [c0, c1, c2] = contexts;
+        self.f32_vars.differentiate([&c0.f32_vars, &c1.f32_vars, &c2.f32_vars], weights, perspective_weights);
+        self.vec2_vars.differentiate([&c0.vec2_vars, &c1.vec2_vars, &c2.vec2_vars], weights, perspective_weights);
+        self.vec3_vars.differentiate([&c0.vec3_vars, &c1.vec3_vars, &c2.vec3_vars], weights, perspective_weights);
+        self.vec4_vars.differentiate([&c0.vec4_vars, &c1.vec4_vars, &c2.vec4_vars], weights, perspective_weights);
     }
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::math::preclude::*;
 
 #[test]
 fn texture_impl_normal() {
@@ -15,3 +16,1339 @@ fn texture_impl_normal() {
     assert_eq!(texture.get_texture_pixels(), &pixels);
 }
 
+
+fn new_framebuffer(width: u32, height: u32) -> framebuffer::FrameBuffer {
+    let mut framebuffer = framebuffer::FrameBuffer::new();
+    let color = texture::Texture::new(texture::TextureFormat::RGBA8, width, height).unwrap();
+    let depth = texture::Texture::new(texture::TextureFormat::DEPTH_FLOAT, width, height).unwrap();
+    framebuffer.attach_texture(framebuffer::AttachmentType::Color(0), Some(Box::new(color))).unwrap();
+    framebuffer.attach_texture(framebuffer::AttachmentType::Depth, Some(Box::new(depth))).unwrap();
+    framebuffer
+}
+
+#[test]
+fn rasterizer_shared_edges_cover_once() {
+    let mut framebuffer = new_framebuffer(32, 32);
+    assert_eq!((framebuffer.get_width(), framebuffer.get_hegiht()), (32, 32));
+
+    // A fan of triangles around an off-grid center, mixing both windings.
+    let center = Vec3::new(13.37, 17.5, 0.);
+    let rim = [
+        Vec3::new(2.25, 3., 0.), Vec3::new(29.5, 2.5, 0.), Vec3::new(30., 16., 0.),
+        Vec3::new(28.75, 29., 0.), Vec3::new(4., 30.5, 0.), Vec3::new(1.5, 17.25, 0.),
+    ];
+    let mut coverage = vec![0; 32 * 32];
+    for i in 0..rim.len() {
+        let a = rim[i];
+        let b = rim[(i + 1) % rim.len()];
+        let triangle = match i % 2 {
+            0 => [center, a, b],
+            _ => [center, b, a],
+        };
+        rasterizer::rasterize_triangle(&mut framebuffer, &render_state::RenderState::default(), &triangle, |fragment, outputs| {
+            let weights = fragment.weights;
+            assert!((weights.x + weights.y + weights.z - 1.).abs() < 1e-5);
+            coverage[(fragment.x + fragment.y * 32) as usize] += 1;
+            outputs[0] = Vec4::new(1., 0., 0., 1.);
+            Some(1)
+        });
+    }
+    // Every pixel center inside the (convex) rim is covered exactly once.
+    for y in 0..32 {
+        for x in 0..32 {
+            let p = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.);
+            let inside = (0..rim.len()).all(|i| {
+                let a = rim[i];
+                let b = rim[(i + 1) % rim.len()];
+                (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x) > 0.
+            });
+            let count = coverage[(x + y * 32) as usize];
+            match inside {
+                true => assert_eq!(count, 1),
+                false => assert_eq!(count, 0),
+            }
+        }
+    }
+}
+
+#[test]
+fn rasterizer_clamps_to_framebuffer() {
+    let mut framebuffer = new_framebuffer(8, 4);
+    let triangle = [Vec3::new(-50., -50., 0.5), Vec3::new(100., -50., 0.5), Vec3::new(-50., 100., 0.5)];
+    let mut count = 0;
+    rasterizer::rasterize_triangle(&mut framebuffer, &render_state::RenderState::default(), &triangle, |_, outputs| {
+        count += 1;
+        outputs[0] = Vec4::new(0., 1., 0., 1.);
+        Some(1)
+    });
+    assert_eq!(count, 8 * 4);
+
+    let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+    assert!(color.pixels.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]));
+    let depth = framebuffer.get_attachment(framebuffer::AttachmentType::Depth).unwrap();
+    assert!(depth.pixels.chunks_exact(4).all(|bytes| bytes == 0.5f32.to_ne_bytes()));
+
+    // Huge coordinates must not overflow the edge functions.
+    let triangle = [Vec3::new(-1e12, -1e12, 0.25), Vec3::new(3e12, -1e12, 0.25), Vec3::new(-1e12, 3e12, 0.25)];
+    let mut count = 0;
+    rasterizer::rasterize_triangle(&mut framebuffer, &render_state::RenderState::default(), &triangle, |_, _| {
+        count += 1;
+        Some(1)
+    });
+    assert_eq!(count, 8 * 4);
+}
+
+struct ColorShader;
+
+impl shader::Shader for ColorShader {
+    type Vertex = (Vec4, Vec3);
+
+    fn vertex(&self, output: &mut shader_context::ShaderContext, vertex: &Self::Vertex) -> Vec4 {
+        *output.alloc_vec3(0) = vertex.1;
+        vertex.0
+    }
+
+    fn fragment(&self, input: &mut shader_context::ShaderContext) -> Option<Vec4> {
+        let color = *input.get_vec3(0);
+        // Discard the fragments in the blue corner.
+        match color.z > 0.5 {
+            true => None,
+            false => Some(color.into_vec4(1.)),
+        }
+    }
+}
+
+#[test]
+fn shader_pipeline_interpolates_varyings() {
+    let mut framebuffer = new_framebuffer(16, 16);
+    let vertices = [
+        (Vec4::new(-1., -1., 0., 1.), Vec3::new(1., 0., 0.)),
+        (Vec4::new(1., -1., 0., 1.), Vec3::new(0., 1., 0.)),
+        (Vec4::new(-1., 1., 0., 1.), Vec3::new(0., 0., 1.)),
+    ];
+    rasterizer::draw_arrays(&mut framebuffer, &render_state::RenderState::default(), &ColorShader, &vertices);
+
+    let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+    let pixel = |x: usize, y: usize| &color.pixels[(x + y * 16) * 4..(x + y * 16) * 4 + 4];
+    // Near the red and green vertices.
+    assert_eq!(pixel(0, 0), [239, 8, 8, 255]);
+    assert_eq!(pixel(14, 0), [16, 231, 8, 255]);
+    // Discarded near the blue vertex, the initial texture content remains.
+    assert_eq!(pixel(0, 14), [255, 255, 255, 255]);
+}
+
+// Draws a floor plane (y = -1, x in [-2, 2], z in [z0, z0 - 8]) with an 8x8 checkerboard.
+struct CheckerShader {
+    mvp: Mat4,
+    mode: shader_context::Interpolation,
+}
+
+impl shader::Shader for CheckerShader {
+    type Vertex = (Vec3, Vec2);
+
+    fn vertex(&self, output: &mut shader_context::ShaderContext, vertex: &Self::Vertex) -> Vec4 {
+        output.set_vec2_interpolation(0, self.mode);
+        *output.alloc_vec2(0) = vertex.1;
+        self.mvp * vertex.0.into_vec4(1.)
+    }
+
+    fn fragment(&self, input: &mut shader_context::ShaderContext) -> Option<Vec4> {
+        let texcoord = *input.get_vec2(0) * 8.;
+        let value = ((texcoord.x.floor() + texcoord.y.floor()) as i32 & 1) as f32;
+        Some(Vec4::new(value, value, value, 1.))
+    }
+}
+
+// Count the pixels whose checker value differs from the analytically projected one.
+fn checker_mismatches(mode: shader_context::Interpolation, z0: f32) -> usize {
+    const SIZE: u32 = 64;
+    let mut framebuffer = new_framebuffer(SIZE, SIZE);
+    let shader = CheckerShader { mvp: Mat4::perspective(PI / 2., 1., 0.1, 100.), mode };
+    let corners = [
+        (Vec3::new(-2., -1., z0), Vec2::new(0., 0.)),
+        (Vec3::new(2., -1., z0), Vec2::new(1., 0.)),
+        (Vec3::new(2., -1., z0 - 8.), Vec2::new(1., 1.)),
+        (Vec3::new(-2., -1., z0 - 8.), Vec2::new(0., 1.)),
+    ];
+    let vertices = [corners[0], corners[1], corners[2], corners[0], corners[2], corners[3]];
+    rasterizer::draw_arrays(&mut framebuffer, &render_state::RenderState::default(), &shader, &vertices);
+
+    let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+    let mut mismatches = 0;
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            // Cast a ray through the pixel center and intersect it with the floor.
+            let ndc_x = (x as f32 + 0.5) / SIZE as f32 * 2. - 1.;
+            let ndc_y = (y as f32 + 0.5) / SIZE as f32 * 2. - 1.;
+            if ndc_y >= 0. {
+                continue;
+            }
+            let t = -1. / ndc_y;
+            let (u, v) = ((ndc_x * t + 2.) / 4., (t + z0) / 8.);
+            let (u, v) = (u * 8., v * 8.);
+            // Skip the pixels off the plane or too close to a checker edge.
+            let margin = 0.1;
+            if u < margin || u > 8. - margin || v < margin || v > 8. - margin
+                || (u - u.round()).abs() < margin || (v - v.round()).abs() < margin {
+                continue;
+            }
+            let expected = ((u.floor() + v.floor()) as u32 & 1) as u8 * 255;
+            if color.pixels[((x + y * SIZE) * 4) as usize] != expected {
+                mismatches += 1;
+            }
+        }
+    }
+    mismatches
+}
+
+#[test]
+fn perspective_correct_checkerboard() {
+    assert_eq!(checker_mismatches(shader_context::Interpolation::Smooth, -2.), 0);
+    // Screen-space interpolation visibly distorts the same checkerboard.
+    assert!(checker_mismatches(shader_context::Interpolation::NoPerspective, -2.) > 100);
+}
+
+#[test]
+fn clipping_interpolates_varyings() {
+    use clipping::{clip_triangle, ClipVertex};
+
+    // Every varying stores the clip-space position, so it must still match after clipping.
+    let vertex = |position: Vec4| {
+        let mut context = shader_context::ShaderContext::new();
+        *context.alloc_f32(0) = position.x;
+        *context.alloc_vec4(1) = position;
+        ClipVertex { position, context }
+    };
+    let polygon = clip_triangle([
+        vertex(Vec4::new(-3., -0.5, 0.2, 1.)),
+        vertex(Vec4::new(0.5, -0.5, -2., 1.)),
+        vertex(Vec4::new(0.5, 3., 0.9, 2.)),
+    ]);
+    assert!(polygon.len() > 3);
+    for mut vertex in polygon {
+        let p = vertex.position;
+        assert!(p.x >= -p.w - 1e-5 && p.x <= p.w + 1e-5);
+        assert!(p.y >= -p.w - 1e-5 && p.y <= p.w + 1e-5);
+        assert!(p.z >= -p.w - 1e-5 && p.z <= p.w + 1e-5);
+        assert!((*vertex.context.get_f32(0) - p.x).abs() < 1e-5);
+        assert!((*vertex.context.get_vec4(1) - p).magnitude() < 1e-5);
+    }
+
+    // A triangle completely outside is rejected.
+    let polygon = clip_triangle([
+        vertex(Vec4::new(2., 0., 0., 1.)),
+        vertex(Vec4::new(3., 0., 0., 1.)),
+        vertex(Vec4::new(2., 1., 0., 1.)),
+    ]);
+    assert!(polygon.is_empty());
+}
+
+#[test]
+fn clipping_near_plane() {
+    // The floor starts behind the camera, the vertices there have w <= 0.
+    assert_eq!(checker_mismatches(shader_context::Interpolation::Smooth, 4.), 0);
+}
+
+#[test]
+fn clipping_keeps_flat_provoking_vertex() {
+    struct FlatShader;
+
+    impl shader::Shader for FlatShader {
+        type Vertex = (Vec4, f32);
+
+        fn vertex(&self, output: &mut shader_context::ShaderContext, vertex: &Self::Vertex) -> Vec4 {
+            *output.alloc_f32(0) = vertex.1;
+            output.set_f32_interpolation(0, shader_context::Interpolation::Flat);
+            vertex.0
+        }
+
+        fn fragment(&self, input: &mut shader_context::ShaderContext) -> Option<Vec4> {
+            let value = *input.get_f32(0);
+            Some(Vec4::new(value, value, value, 1.))
+        }
+    }
+
+    // The first two vertices are outside the left plane, so the first clipped vertex comes from the edge 1 -> 2.
+    let mut framebuffer = new_framebuffer(8, 8);
+    let vertices = [
+        (Vec4::new(-3., 1., 0., 1.), 0.1),
+        (Vec4::new(-3., -1., 0., 1.), 0.5),
+        (Vec4::new(1., 0., 0., 1.), 0.9),
+    ];
+    rasterizer::draw_arrays(&mut framebuffer, &render_state::RenderState::default(), &FlatShader, &vertices);
+
+    let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+    let covered: Vec<_> = color.pixels.chunks_exact(4).filter(|pixel| pixel[3] == 255 && pixel[0] != 255).collect();
+    assert!(!covered.is_empty());
+    assert!(covered.iter().all(|pixel| pixel[..3] == [26, 26, 26]));
+}
+
+#[test]
+fn shader_context_allocation() {
+    use shader_context::{ShaderContext, Varying};
+
+    let mut context = ShaderContext::with_capacity(1);
+    *context.alloc_vec3(5) = Vec3::new(1., 2., 3.);
+    *context.alloc_f32(0) = 1.;
+    *context.alloc_vec3(2) = Vec3::new(4., 5., 6.);
+    // Allocating the same location twice doesn't duplicate it.
+    *context.alloc_f32(0) += 1.;
+    assert_eq!(*context.get_f32(0), 2.);
+    assert_eq!(*context.get_vec3(5), Vec3::new(1., 2., 3.));
+    assert_eq!(
+        context.varyings().collect::<Vec<_>>(),
+        vec![Varying::F32(0), Varying::Vec3(5), Varying::Vec3(2)],
+    );
+
+    // Only the variables allocated by the first vertex are interpolated.
+    let mut other = ShaderContext::new();
+    *other.alloc_vec3(5) = Vec3::new(3., 2., 1.);
+    *other.alloc_vec4(0) = Vec4::new(1., 1., 1., 1.);
+    let mut input = ShaderContext::new();
+    let weights = Vec3::new(0.5, 0.5, 0.);
+    input.interpolate([&context, &other, &other], weights, weights);
+    assert_eq!(input.varyings().count(), 3);
+    assert_eq!(*input.get_vec3(5), Vec3::new(2., 2., 2.));
+    assert_eq!(*input.get_vec3(2), Vec3::new(2., 2.5, 3.));
+
+    context.clear();
+    assert_eq!(context.varyings().count(), 0);
+    let result = std::panic::catch_unwind(move || *context.get_f32(0));
+    assert!(result.is_err());
+}
+
+struct SolidShader {
+    color: Vec4,
+}
+
+impl shader::Shader for SolidShader {
+    type Vertex = Vec4;
+
+    fn vertex(&self, _: &mut shader_context::ShaderContext, vertex: &Self::Vertex) -> Vec4 {
+        *vertex
+    }
+
+    fn fragment(&self, _: &mut shader_context::ShaderContext) -> Option<Vec4> {
+        Some(self.color)
+    }
+}
+
+// Two triangles covering the whole framebuffer at the NDC depth z.
+fn fullscreen_quad(z: f32) -> [Vec4; 6] {
+    let corners = [
+        Vec4::new(-1., -1., z, 1.), Vec4::new(1., -1., z, 1.),
+        Vec4::new(1., 1., z, 1.), Vec4::new(-1., 1., z, 1.),
+    ];
+    [corners[0], corners[1], corners[2], corners[0], corners[2], corners[3]]
+}
+
+fn read_pixel(framebuffer: &framebuffer::FrameBuffer, x: u32, y: u32) -> (Vec<u8>, f32) {
+    let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+    let depth = framebuffer.get_attachment(framebuffer::AttachmentType::Depth).unwrap();
+    let offset = ((x + y * color.width) * 4) as usize;
+    (color.pixels[offset..offset + 4].to_vec(), depth.get_depth(x, y))
+}
+
+#[test]
+fn depth_test_and_write_mask() {
+    use render_state::{CompareFunc, RenderState};
+
+    let red = SolidShader { color: Vec4::new(1., 0., 0., 1.) };
+    let green = SolidShader { color: Vec4::new(0., 1., 0., 1.) };
+    let mut state = RenderState::default();
+
+    let mut framebuffer = new_framebuffer(4, 4);
+    rasterizer::draw_arrays(&mut framebuffer, &state, &red, &fullscreen_quad(-0.5));
+    rasterizer::draw_arrays(&mut framebuffer, &state, &green, &fullscreen_quad(0.5));
+    assert_eq!(read_pixel(&framebuffer, 1, 2), (vec![255, 0, 0, 255], 0.25));
+
+    state.depth.func = CompareFunc::Greater;
+    state.depth.write_enabled = false;
+    rasterizer::draw_arrays(&mut framebuffer, &state, &green, &fullscreen_quad(0.5));
+    assert_eq!(read_pixel(&framebuffer, 1, 2), (vec![0, 255, 0, 255], 0.25));
+
+    state.depth.func = CompareFunc::Never;
+    rasterizer::draw_arrays(&mut framebuffer, &state, &red, &fullscreen_quad(0.));
+    assert_eq!(read_pixel(&framebuffer, 3, 3), (vec![0, 255, 0, 255], 0.25));
+}
+
+#[test]
+fn depth_range_and_bias() {
+    use render_state::RenderState;
+
+    let red = SolidShader { color: Vec4::new(1., 0., 0., 1.) };
+    let mut state = RenderState::default();
+    state.depth.bias.constant = 1.;
+
+    let mut framebuffer = new_framebuffer(4, 4);
+    framebuffer.set_viewport(Some(framebuffer::Viewport {
+        x: 0, y: 0, width: 4, height: 4, min_depth: 0.5, max_depth: 1.,
+    }));
+    rasterizer::draw_arrays(&mut framebuffer, &state, &red, &fullscreen_quad(0.));
+    assert_eq!(read_pixel(&framebuffer, 2, 1).1, 0.75 + 2f32.powi(-24));
+
+    // Depth increases by 1/16 per pixel along x, offset by twice the slope.
+    let mut state = RenderState::default();
+    state.depth.bias.slope_scaled = 2.;
+    let mut framebuffer = new_framebuffer(16, 16);
+    let triangle = [Vec3::new(0., 0., 0.), Vec3::new(16., 0., 1.), Vec3::new(0., 16., 0.)];
+    rasterizer::rasterize_triangle(&mut framebuffer, &state, &triangle, |_, outputs| {
+        outputs[0] = Vec4::new(1., 1., 1., 1.);
+        Some(1)
+    });
+    assert!((read_pixel(&framebuffer, 3, 0).1 - (3.5 / 16. + 0.125)).abs() < 1e-6);
+}
+
+#[test]
+fn depth_texture_storage() {
+    use texture::{Texture, TextureFormat};
+
+    let mut depth = Texture::new(TextureFormat::DEPTH_FLOAT, 3, 2).unwrap();
+    assert_eq!(depth.pixels.len(), 3 * 2 * 4);
+    assert_eq!(depth.get_depth(2, 1), 1.);
+    depth.set_depth(2, 1, 0.3);
+    depth.set_depth(0, 1, 0.6);
+    assert_eq!(depth.get_depth(2, 1), 0.3);
+    assert_eq!(depth.get_depth(1, 1), 1.);
+    assert_eq!(depth.texture_sample(Vec2::new(0.9, 0.9)), Vec4::new(0.3, 0.3, 0.3, 1.));
+    assert_eq!(depth.texture_sample(Vec2::new(0.1, 0.9)), Vec4::new(0.6, 0.6, 0.6, 1.));
+
+    let mut framebuffer = framebuffer::FrameBuffer::new();
+    framebuffer.attach_texture(framebuffer::AttachmentType::Depth, Some(Box::new(depth))).unwrap();
+    framebuffer.clear();
+    let depth = framebuffer.get_attachment(framebuffer::AttachmentType::Depth).unwrap();
+    assert!((0..2).all(|y| (0..3).all(|x| depth.get_depth(x, y) == 1.)));
+}
+
+#[test]
+fn blend_equations() {
+    use render_state::{BlendComponent, BlendEquation, BlendFactor, BlendState};
+
+    let src = Vec4::new(0.8, 0.2, 0.4, 0.25);
+    let dst = Vec4::new(0.4, 0.6, 0.4, 1.);
+    assert_eq!(BlendState::default().blend(src, dst), src);
+
+    let mut state = BlendState::alpha_blending();
+    assert_eq!(state.blend(src, dst), Vec4::new(0.5, 0.5, 0.4, 1.));
+
+    state.color = BlendComponent {
+        src_factor: BlendFactor::ConstantColor,
+        dst_factor: BlendFactor::DstColor,
+        equation: BlendEquation::ReverseSubtract,
+    };
+    state.alpha.equation = BlendEquation::Min;
+    state.constant = Vec4::new(0.5, 0.5, 0.5, 0.);
+    assert!((state.blend(src, dst) - Vec4::new(-0.24, 0.26, -0.04, 0.25)).magnitude() < 1e-6);
+
+    state.color.equation = BlendEquation::Max;
+    assert_eq!(state.blend(src, dst), Vec4::new(0.8, 0.6, 0.4, 0.25));
+}
+
+#[test]
+fn blending_in_linear_space() {
+    use render_state::{BlendState, RenderState};
+    use texture::{Texture, TextureFormat};
+
+    let black = SolidShader { color: Vec4::new(0., 0., 0., 0.5) };
+    let state = RenderState::default();
+
+    // Both attachments start white, half of the black fragment is blended over them.
+    let mut framebuffer = new_framebuffer(2, 2);
+    framebuffer.set_blend_state(BlendState::alpha_blending());
+    rasterizer::draw_arrays(&mut framebuffer, &state, &black, &fullscreen_quad(0.));
+    assert_eq!(read_pixel(&framebuffer, 0, 0).0, vec![128, 128, 128, 255]);
+
+    let mut framebuffer = framebuffer::FrameBuffer::new();
+    let color = Texture::new(TextureFormat::SRGB8_A8, 2, 2).unwrap();
+    framebuffer.attach_texture(framebuffer::AttachmentType::Color(0), Some(Box::new(color))).unwrap();
+    framebuffer.set_blend_state(BlendState::alpha_blending());
+    rasterizer::draw_arrays(&mut framebuffer, &state, &black, &fullscreen_quad(0.));
+    let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+    // The linear 0.5 encoded to sRGB.
+    assert_eq!(color.pixels[0..4], [186, 186, 186, 255]);
+}
+
+struct FacingShader;
+
+impl shader::Shader for FacingShader {
+    type Vertex = Vec4;
+
+    fn vertex(&self, _: &mut shader_context::ShaderContext, vertex: &Self::Vertex) -> Vec4 {
+        *vertex
+    }
+
+    fn fragment(&self, input: &mut shader_context::ShaderContext) -> Option<Vec4> {
+        match input.is_front_facing() {
+            true => Some(Vec4::new(0., 1., 0., 1.)),
+            false => Some(Vec4::new(1., 0., 0., 1.)),
+        }
+    }
+}
+
+#[test]
+fn face_culling() {
+    use render_state::{CullMode, FrontFace, RenderState};
+
+    // The left half is counter-clockwise, the right half clockwise.
+    let vertices = [
+        Vec4::new(-1., -1., 0., 1.), Vec4::new(0., -1., 0., 1.), Vec4::new(-1., 1., 0., 1.),
+        Vec4::new(0., -1., 0., 1.), Vec4::new(0., 1., 0., 1.), Vec4::new(1., -1., 0., 1.),
+    ];
+    let draw = |state: &RenderState| {
+        let mut framebuffer = new_framebuffer(4, 4);
+        rasterizer::draw_arrays(&mut framebuffer, state, &FacingShader, &vertices);
+        (read_pixel(&framebuffer, 0, 0).0, read_pixel(&framebuffer, 2, 0).0)
+    };
+    let green = vec![0, 255, 0, 255];
+    let red = vec![255, 0, 0, 255];
+    let white = vec![255; 4];
+
+    let mut state = RenderState::default();
+    assert_eq!(draw(&state), (green.clone(), red.clone()));
+    state.rasterizer.cull_mode = CullMode::Back;
+    assert_eq!(draw(&state), (green.clone(), white.clone()));
+    state.rasterizer.cull_mode = CullMode::Front;
+    assert_eq!(draw(&state), (white.clone(), red.clone()));
+    state.rasterizer.front_face = FrontFace::Clockwise;
+    assert_eq!(draw(&state), (red, white));
+}
+
+#[test]
+fn viewport_and_scissor() {
+    use framebuffer::{Rect, Viewport};
+    use render_state::RenderState;
+
+    let red = SolidShader { color: Vec4::new(1., 0., 0., 1.) };
+    let green = SolidShader { color: Vec4::new(0., 1., 0., 1.) };
+    let state = RenderState::default();
+    let mut framebuffer = new_framebuffer(8, 4);
+    assert_eq!(framebuffer.get_viewport().width, 8);
+
+    // Split screen: the left half is red and the right half green.
+    let mut viewport = Viewport { x: 0, y: 0, width: 4, height: 4, min_depth: 0., max_depth: 1. };
+    framebuffer.set_viewport(Some(viewport));
+    rasterizer::draw_arrays(&mut framebuffer, &state, &red, &fullscreen_quad(0.));
+    viewport.x = 4;
+    framebuffer.set_viewport(Some(viewport));
+    rasterizer::draw_arrays(&mut framebuffer, &state, &green, &fullscreen_quad(-0.5));
+    assert_eq!(read_pixel(&framebuffer, 3, 3), (vec![255, 0, 0, 255], 0.5));
+    assert_eq!(read_pixel(&framebuffer, 4, 0), (vec![0, 255, 0, 255], 0.25));
+
+    // Only the scissor rectangle is touched.
+    framebuffer.set_viewport(None);
+    framebuffer.set_scissor(Some(Rect { x: 1, y: 1, width: 2, height: 1 }));
+    rasterizer::draw_arrays(&mut framebuffer, &state, &green, &fullscreen_quad(-1.));
+    assert_eq!(read_pixel(&framebuffer, 1, 1), (vec![0, 255, 0, 255], 0.));
+    assert_eq!(read_pixel(&framebuffer, 2, 1), (vec![0, 255, 0, 255], 0.));
+    assert_eq!(read_pixel(&framebuffer, 3, 1), (vec![255, 0, 0, 255], 0.5));
+    assert_eq!(read_pixel(&framebuffer, 1, 2), (vec![255, 0, 0, 255], 0.5));
+
+    // A viewport far larger than the framebuffer.
+    framebuffer.set_scissor(None);
+    viewport = Viewport { x: i32::MIN, y: i32::MIN, width: u32::MAX, height: u32::MAX, min_depth: 0., max_depth: 1. };
+    framebuffer.set_viewport(Some(viewport));
+    rasterizer::draw_arrays(&mut framebuffer, &state, &red, &fullscreen_quad(-1.));
+    assert_eq!(read_pixel(&framebuffer, 0, 0), (vec![255, 0, 0, 255], 0.));
+    assert_eq!(read_pixel(&framebuffer, 7, 3), (vec![255, 0, 0, 255], 0.));
+}
+
+#[test]
+fn stencil_operations() {
+    use render_state::{StencilFaceState, StencilOp};
+
+    assert_eq!(StencilOp::IncrSat.apply(255, 0), 255);
+    assert_eq!(StencilOp::IncrWrap.apply(255, 0), 0);
+    assert_eq!(StencilOp::DecrSat.apply(0, 0), 0);
+    assert_eq!(StencilOp::DecrWrap.apply(0, 0), 255);
+    assert_eq!(StencilOp::Invert.apply(0b1010_0000, 0), 0b0101_1111);
+    assert_eq!(StencilOp::Replace.apply(3, 7), 7);
+
+    let face = StencilFaceState {
+        func: render_state::CompareFunc::Equal,
+        reference: 0b0001_0011,
+        read_mask: 0b0000_1111,
+        write_mask: 0b1111_0000,
+        ..StencilFaceState::default()
+    };
+    assert!(face.test(0b1110_0011));
+    assert!(!face.test(0b0001_0001));
+    assert_eq!(face.update(StencilOp::Replace, 0b0000_1111), 0b0001_1111);
+}
+
+#[test]
+fn stencil_test_masks_drawing() {
+    use render_state::{CompareFunc, RenderState, StencilOp};
+    use texture::{Texture, TextureFormat};
+
+    let red = SolidShader { color: Vec4::new(1., 0., 0., 1.) };
+    let mut framebuffer = new_framebuffer(4, 4);
+    let stencil = Texture::new(TextureFormat::STENCIL8, 4, 4).unwrap();
+    framebuffer.attach_texture(framebuffer::AttachmentType::Stencil, Some(Box::new(stencil))).unwrap();
+
+    // Mark the left half with 1 without touching color or depth.
+    let mut state = RenderState::default();
+    state.stencil.enabled = true;
+    state.stencil.front.pass_op = StencilOp::Replace;
+    state.stencil.front.reference = 1;
+    state.depth.func = CompareFunc::Always;
+    state.depth.write_enabled = false;
+    framebuffer.set_scissor(Some(framebuffer::Rect { x: 0, y: 0, width: 2, height: 4 }));
+    rasterizer::draw_arrays(&mut framebuffer, &state, &red, &fullscreen_quad(0.));
+    framebuffer.set_scissor(None);
+
+    // Draw the whole screen where the stencil equals 1, count the depth failures elsewhere.
+    let mut state = RenderState::default();
+    state.stencil.enabled = true;
+    state.stencil.front.func = CompareFunc::NotEqual;
+    state.stencil.front.reference = 1;
+    state.stencil.front.fail_op = StencilOp::IncrSat;
+    state.stencil.front.depth_fail_op = StencilOp::Invert;
+    state.depth.func = CompareFunc::Greater;
+    let green = SolidShader { color: Vec4::new(0., 1., 0., 1.) };
+    rasterizer::draw_arrays(&mut framebuffer, &state, &green, &fullscreen_quad(0.));
+
+    let stencil = framebuffer.get_attachment(framebuffer::AttachmentType::Stencil).unwrap();
+    assert_eq!(stencil.get_stencil(1, 3), 2);
+    assert_eq!(stencil.get_stencil(2, 3), 255);
+    assert_eq!(read_pixel(&framebuffer, 1, 3), (vec![255, 0, 0, 255], 1.));
+    assert_eq!(read_pixel(&framebuffer, 2, 3), (vec![255; 4], 1.));
+
+    // The back faces use their own state.
+    state.stencil.front.fail_op = StencilOp::Keep;
+    state.stencil.back.pass_op = StencilOp::Zero;
+    state.depth.func = CompareFunc::Always;
+    let mut quad = fullscreen_quad(0.);
+    quad.reverse();
+    rasterizer::draw_arrays(&mut framebuffer, &state, &red, &quad);
+    let stencil = framebuffer.get_attachment(framebuffer::AttachmentType::Stencil).unwrap();
+    assert_eq!(stencil.get_stencil(1, 3), 0);
+    assert_eq!(read_pixel(&framebuffer, 3, 0), (vec![255, 0, 0, 255], 0.5));
+}
+
+struct TargetsShader;
+
+impl shader::Shader for TargetsShader {
+    type Vertex = Vec4;
+
+    fn vertex(&self, _: &mut shader_context::ShaderContext, vertex: &Self::Vertex) -> Vec4 {
+        *vertex
+    }
+
+    fn fragment(&self, _: &mut shader_context::ShaderContext) -> Option<Vec4> {
+        Some(Vec4::new(1., 0., 0., 1.))
+    }
+
+    fn fragment_targets(&self, _: &mut shader_context::ShaderContext, outputs: &mut [Vec4; framebuffer::MAX_COLOR_ATTACHMENTS]) -> Option<usize> {
+        outputs[0] = Vec4::new(1., 0., 0., 1.);
+        outputs[1] = Vec4::new(0., 1., 0., 0.5);
+        outputs[2] = Vec4::new(0., 0., 1., 1.);
+        Some(3)
+    }
+}
+
+#[test]
+fn multiple_render_targets() {
+    use render_state::{BlendState, ColorWriteMask, RenderState};
+    use framebuffer::AttachmentType;
+
+    let mut framebuffer = new_framebuffer(8, 8);
+    for (index, size) in [(1, 4), (2, 6), (3, 8)] {
+        let texture = texture::Texture::new(texture::TextureFormat::RGBA8, size, size).unwrap();
+        framebuffer.attach_texture(AttachmentType::Color(index), Some(Box::new(texture))).unwrap();
+    }
+    // The framebuffer shrinks to the smallest attachment.
+    assert_eq!((framebuffer.get_width(), framebuffer.get_hegiht()), (4, 4));
+    // Out-of-range attachments are rejected.
+    let texture = texture::Texture::new(texture::TextureFormat::RGBA8, 2, 2).unwrap();
+    let attachment = AttachmentType::Color(framebuffer::MAX_COLOR_ATTACHMENTS);
+    let result = framebuffer.attach_texture(attachment, Some(Box::new(texture)));
+    assert_eq!(result, Err(crate::error::RenderError::InvalidAttachment(attachment)));
+    assert_eq!((framebuffer.get_width(), framebuffer.get_hegiht()), (4, 4));
+    // Out of range indices are rejected instead of panicking.
+    assert!(framebuffer.get_attachment(attachment).is_none());
+    assert!(framebuffer.get_attachment_mut(attachment).is_none());
+    assert!(framebuffer.detach_texture(attachment).is_none());
+    assert!(framebuffer.get_blend_state(framebuffer::MAX_COLOR_ATTACHMENTS).is_none());
+    assert!(framebuffer.get_color_mask(framebuffer::MAX_COLOR_ATTACHMENTS).is_none());
+    let mask = ColorWriteMask::default();
+    assert_eq!(framebuffer.set_color_mask_indexed(framebuffer::MAX_COLOR_ATTACHMENTS, mask),
+        Err(crate::error::RenderError::InvalidAttachment(attachment)));
+    assert_eq!(framebuffer.set_blend_state_indexed(framebuffer::MAX_COLOR_ATTACHMENTS, BlendState::alpha_blending()),
+        Err(crate::error::RenderError::InvalidAttachment(attachment)));
+
+    framebuffer.clear();
+    framebuffer.set_blend_state_indexed(1, BlendState::alpha_blending()).unwrap();
+    framebuffer.set_color_mask_indexed(2, ColorWriteMask { red: true, green: true, blue: false, alpha: false }).unwrap();
+    rasterizer::draw_arrays(&mut framebuffer, &RenderState::default(), &TargetsShader, &fullscreen_quad(0.));
+
+    let read = |index: usize| {
+        let color = framebuffer.get_attachment(AttachmentType::Color(index)).unwrap();
+        color.pixels[0..4].to_vec()
+    };
+    assert_eq!(read(0), [255, 0, 0, 255]);
+    // Blended over the cleared black with alpha 0.5.
+    assert_eq!(read(1)[1], 128);
+    // Blue and alpha are masked out.
+    assert_eq!(read(2), [0, 0, 0, 0]);
+    // No output for the fourth attachment.
+    assert_eq!(read(3), [0, 0, 0, 0]);
+}
+
+#[test]
+fn clear_values_and_scissor() {
+    use framebuffer::AttachmentType;
+
+    let mut first = new_framebuffer(4, 4);
+    let mut second = new_framebuffer(4, 4);
+    let stencil = texture::Texture::new(texture::TextureFormat::STENCIL8, 4, 4).unwrap();
+    second.attach_texture(AttachmentType::Stencil, Some(Box::new(stencil))).unwrap();
+    first.set_clear_color(1., 0., 0., 1.);
+    second.set_clear_color(0., 0., 1., 0.);
+    second.set_clear_depth(0.25);
+    second.set_clear_stencil(7);
+    first.clear();
+    second.clear();
+    assert_eq!(read_pixel(&first, 3, 3), (vec![255, 0, 0, 255], 1.));
+    assert_eq!(read_pixel(&second, 3, 3), (vec![0, 0, 255, 0], 0.25));
+    assert_eq!(second.get_attachment(AttachmentType::Stencil).unwrap().get_stencil(3, 3), 7);
+
+    // Partial clears only touch the scissor rectangle, one attachment at a time.
+    first.set_scissor(Some(framebuffer::Rect { x: 1, y: 2, width: 8, height: 1 }));
+    first.set_clear_color(0., 1., 0., 1.);
+    first.set_clear_depth(0.5);
+    first.clear_color();
+    assert_eq!(read_pixel(&first, 1, 2), (vec![0, 255, 0, 255], 1.));
+    assert_eq!(read_pixel(&first, 3, 2), (vec![0, 255, 0, 255], 1.));
+    assert_eq!(read_pixel(&first, 0, 2), (vec![255, 0, 0, 255], 1.));
+    assert_eq!(read_pixel(&first, 1, 3), (vec![255, 0, 0, 255], 1.));
+    first.clear_depth();
+    assert_eq!(read_pixel(&first, 2, 2).1, 0.5);
+    assert_eq!(read_pixel(&first, 2, 1).1, 1.);
+}
+
+#[test]
+fn texture_and_framebuffer_errors() {
+    use crate::error::RenderError;
+    use framebuffer::{AttachmentType, FramebufferStatus};
+    use texture::{Texture, TextureFormat};
+
+    assert_eq!(Texture::new(TextureFormat::RGBA8, 0, 4).unwrap_err(), RenderError::InvalidDimensions { width: 0, height: 4 });
+    assert_eq!(Texture::new(TextureFormat::RGBA8, 70000, 70000).unwrap_err(),
+        RenderError::InvalidDimensions { width: 70000, height: 70000 });
+    assert!(Texture::new(TextureFormat::R8, texture::MAX_TEXTURE_SIZE + 1, 1).is_err());
+    assert_eq!(TextureFormat::RGBA32F.data_size(u32::MAX, u32::MAX), None);
+    let mut texture = Texture::new(TextureFormat::RGBA8, 2, 2).unwrap();
+    assert_eq!(texture.set_texture_pixels(&[0; 17]), Err(RenderError::SizeMismatch { expected: 16, actual: 17 }));
+
+    let mut framebuffer = framebuffer::FrameBuffer::new();
+    assert_eq!(framebuffer.check_status(), FramebufferStatus::MissingAttachment);
+    assert_eq!(framebuffer.validate(), Err(RenderError::IncompleteFramebuffer(FramebufferStatus::MissingAttachment)));
+    // Detaching an empty attachment is not an error.
+    framebuffer.attach_texture(AttachmentType::Depth, None).unwrap();
+
+    let depth = Texture::new(TextureFormat::DEPTH_FLOAT, 4, 2).unwrap();
+    let result = framebuffer.attach_texture(AttachmentType::Color(0), Some(Box::new(depth)));
+    assert_eq!(result, Err(RenderError::FormatMismatch { attachment: AttachmentType::Color(0), format: TextureFormat::DEPTH_FLOAT }));
+    assert!(framebuffer.get_attachment(AttachmentType::Color(0)).is_none());
+
+    // A single depth attachment is enough, and detaching it empties the framebuffer again.
+    let depth = Texture::new(TextureFormat::DEPTH_FLOAT, 4, 2).unwrap();
+    framebuffer.attach_texture(AttachmentType::Depth, Some(Box::new(depth))).unwrap();
+    assert_eq!(framebuffer.check_status(), FramebufferStatus::Complete);
+    assert_eq!((framebuffer.get_width(), framebuffer.get_hegiht()), (4, 2));
+    framebuffer.attach_texture(AttachmentType::Depth, None).unwrap();
+    assert_eq!((framebuffer.get_width(), framebuffer.get_hegiht()), (0, 0));
+}
+
+#[test]
+fn sampler_filters_and_wrap_modes() {
+    use sampler::{Filter, Sampler, WrapMode};
+
+    // A 4x1 gradient: 0, 85, 170, 255.
+    let mut texture = texture::Texture::new(texture::TextureFormat::R8, 4, 1).unwrap();
+    texture.pixels.copy_from_slice(&[0, 85, 170, 255]);
+    let sample = |sampler: &Sampler, u: f32| sampler.sample(&texture, Vec2::new(u, 0.5)).x;
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-6;
+
+    let nearest = Sampler::new(Filter::Nearest, WrapMode::Repeat);
+    assert_eq!(sample(&nearest, 0.3), 1. / 3.);
+    assert_eq!(sample(&nearest, 1.3), 1. / 3.);
+    assert_eq!(sample(&nearest, -0.1), 1.);
+
+    // Texel centers sample exactly, halfway between them is the average.
+    let linear = Sampler::new(Filter::Linear, WrapMode::Repeat);
+    assert!(close(sample(&linear, 0.375), 1. / 3.));
+    assert!(close(sample(&linear, 0.5), 0.5));
+    // Across the seam the first and last texels are blended.
+    assert!(close(sample(&linear, 0.), 0.5));
+
+    let clamp = Sampler::new(Filter::Linear, WrapMode::ClampToEdge);
+    assert!(close(sample(&clamp, 0.), 0.));
+    assert!(close(sample(&clamp, 2.), 1.));
+
+    let mirrored = Sampler::new(Filter::Nearest, WrapMode::MirroredRepeat);
+    assert!(close(sample(&mirrored, 1.1), 1.));
+    assert!(close(sample(&mirrored, 1.9), 0.));
+    assert!(close(sample(&mirrored, -0.1), 0.));
+
+    let mut border = Sampler::new(Filter::Linear, WrapMode::ClampToBorder);
+    border.border_color = Vec4::new(1., 1., 1., 1.);
+    assert!(close(sample(&border, 0.), 0.5));
+    assert_eq!(border.sample(&texture, Vec2::new(1.5, 0.5)), border.border_color);
+    // Wrap modes are per axis: v is clamped while u repeats.
+    let mut mixed = border;
+    mixed.wrap_s = WrapMode::Repeat;
+    mixed.wrap_t = WrapMode::ClampToEdge;
+    mixed.mag_filter = Filter::Nearest;
+    assert!(close(mixed.sample(&texture, Vec2::new(1.9, 3.)).x, 1.));
+
+    // The default sampler keeps the behavior of Texture::texture_sample.
+    assert_eq!(texture.texture_sample(Vec2::new(2., -1.)).x, 1.);
+}
+
+fn checker_texture(size: u32) -> texture::Texture {
+    let mut texture = texture::Texture::new(texture::TextureFormat::R8, size, size).unwrap();
+    for y in 0..size {
+        for x in 0..size {
+            texture.pixels[(x + y * size) as usize] = (((x + y) % 2) * 255) as u8;
+        }
+    }
+    texture
+}
+
+#[test]
+fn mipmap_generation() {
+    use texture::{Texture, TextureFormat};
+
+    let mut texture = checker_texture(4);
+    texture.generate_mipmaps().unwrap();
+    assert_eq!(texture.get_level_count(), 3);
+    assert_eq!(texture.get_level(1).get_shape(), (2, 2));
+    assert!(texture.get_level(1).pixels.iter().all(|&value| value == 128));
+    assert_eq!(texture.get_level(2).pixels, [128]);
+
+    let mut texture = Texture::new(TextureFormat::RGBA8, 4, 1).unwrap();
+    texture.generate_mipmaps().unwrap();
+    assert_eq!(texture.get_level_count(), 3);
+    assert_eq!(texture.get_level(2).get_shape(), (1, 1));
+
+    // Black and white average to half the intensity in linear space.
+    let mut texture = Texture::new(TextureFormat::SRGB8_A8, 2, 1).unwrap();
+    texture.pixels.copy_from_slice(&[0, 0, 0, 0, 255, 255, 255, 255]);
+    texture.generate_mipmaps().unwrap();
+    assert_eq!(texture.get_level(1).pixels, [186, 186, 186, 128]);
+
+    let mut texture = Texture::new(TextureFormat::STENCIL8, 2, 2).unwrap();
+    assert!(texture.generate_mipmaps().is_err());
+}
+
+struct TexturedShader {
+    texture: texture::Texture,
+    sampler: sampler::Sampler,
+}
+
+impl shader::Shader for TexturedShader {
+    type Vertex = (Vec4, Vec2);
+
+    fn vertex(&self, output: &mut shader_context::ShaderContext, vertex: &Self::Vertex) -> Vec4 {
+        *output.alloc_vec2(0) = vertex.1;
+        vertex.0
+    }
+
+    fn fragment(&self, input: &mut shader_context::ShaderContext) -> Option<Vec4> {
+        let texcoord = *input.get_vec2(0);
+        let (ddx, ddy) = input.get_vec2_derivatives(0);
+        Some(self.sampler.sample_grad(&self.texture, texcoord, ddx, ddy))
+    }
+}
+
+#[test]
+fn trilinear_sampling_with_quad_derivatives() {
+    use sampler::{Filter, MipmapFilter, Sampler, WrapMode};
+
+    let mut texture = checker_texture(4);
+    texture.generate_mipmaps().unwrap();
+    let mut sampler = Sampler::new(Filter::Linear, WrapMode::Repeat);
+    sampler.mipmap_filter = MipmapFilter::Linear;
+    // Between a texel center of the checkerboard and the uniform gray of level 1.
+    assert!((sampler.sample_lod(&texture, Vec2::new(0.125, 0.125), 0.5).x - (0.5 * 128. / 255.)).abs() < 1e-6);
+    assert_eq!(sampler.sample_lod(&texture, Vec2::new(0.125, 0.125), -1.).x, 0.);
+    // A NaN LOD samples the base level, with or without mip levels.
+    assert_eq!(sampler.sample_lod(&texture, Vec2::new(0.125, 0.125), f32::NAN).x, 0.);
+    assert_eq!(sampler.sample_lod(&checker_texture(4), Vec2::new(0.125, 0.125), f32::NAN).x, 0.);
+
+    let dx = Vec2::new(0.25, 0.);
+    assert_eq!(Sampler::compute_lod(&texture, dx, Vec2::new(0., 0.125)), 0.);
+
+    // A 32x32 checkerboard mapped onto 8x8 pixels: 4 texels per pixel, LOD 2.
+    let corners = [
+        (Vec4::new(-1., -1., 0., 1.), Vec2::new(0., 0.)), (Vec4::new(1., -1., 0., 1.), Vec2::new(1., 0.)),
+        (Vec4::new(1., 1., 0., 1.), Vec2::new(1., 1.)), (Vec4::new(-1., 1., 0., 1.), Vec2::new(0., 1.)),
+    ];
+    let quad = [corners[0], corners[1], corners[2], corners[0], corners[2], corners[3]];
+    let render = |shader: &TexturedShader| {
+        let mut framebuffer = new_framebuffer(8, 8);
+        rasterizer::draw_arrays(&mut framebuffer, &render_state::RenderState::default(), shader, &quad);
+        let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+        color.pixels.chunks_exact(4).map(|pixel| pixel[0]).collect::<Vec<_>>()
+    };
+
+    let mut texture = checker_texture(32);
+    texture.generate_mipmaps().unwrap();
+    let mut shader = TexturedShader { texture, sampler: Sampler::new(Filter::Nearest, WrapMode::Repeat) };
+    // Without mipmaps every pixel hits the same texel of a 4x4 block and aliases.
+    let aliased = render(&shader);
+    assert!(aliased.iter().all(|&value| value == aliased[0]));
+    assert!(aliased[0] == 0 || aliased[0] == 255);
+
+    shader.sampler.mipmap_filter = MipmapFilter::Linear;
+    let filtered = render(&shader);
+    assert!(filtered.iter().all(|&value| value.abs_diff(128) <= 1));
+}
+
+#[test]
+fn anisotropic_filtering_matches_reference() {
+    use sampler::{Filter, MipmapFilter, Sampler, WrapMode};
+
+    // Stripes 4 texels wide along u, constant along v.
+    let mut texture = texture::Texture::new(texture::TextureFormat::R8, 32, 32).unwrap();
+    for y in 0..32 {
+        for x in 0..32 {
+            texture.pixels[(x + y * 32) as usize] = (((x / 4) % 2) * 255) as u8;
+        }
+    }
+    texture.generate_mipmaps().unwrap();
+
+    // A floor receding from w = 1 to w = 16, the stripes run away from the viewer.
+    let corners = [
+        (Vec4::new(-1., -1., 0., 1.), Vec2::new(0., 0.)), (Vec4::new(1., -1., 0., 1.), Vec2::new(1., 0.)),
+        (Vec4::new(16., 16., 0., 16.), Vec2::new(1., 8.)), (Vec4::new(-16., 16., 0., 16.), Vec2::new(0., 8.)),
+    ];
+    let floor = [corners[0], corners[1], corners[2], corners[0], corners[2], corners[3]];
+    let render = |shader: &TexturedShader, size: u32| {
+        let mut framebuffer = new_framebuffer(size, size);
+        rasterizer::draw_arrays(&mut framebuffer, &render_state::RenderState::default(), shader, &floor);
+        let color = framebuffer.get_attachment(framebuffer::AttachmentType::Color(0)).unwrap();
+        color.pixels.chunks_exact(4).map(|pixel| pixel[0] as f32).collect::<Vec<_>>()
+    };
+
+    // The reference is rendered from the base level at 8x8 samples per pixel.
+    let mut shader = TexturedShader { texture, sampler: Sampler::new(Filter::Linear, WrapMode::Repeat) };
+    let supersampled = render(&shader, 256);
+    let mut reference = vec![0.; 32 * 32];
+    for (i, value) in supersampled.iter().enumerate() {
+        let (x, y) = (i % 256 / 8, i / 256 / 8);
+        reference[x + y * 32] += value / 64.;
+    }
+    let error = |image: &[f32]| {
+        image.iter().zip(reference.iter()).map(|(a, b)| (a - b).abs()).sum::<f32>() / image.len() as f32
+    };
+
+    shader.sampler.mipmap_filter = MipmapFilter::Linear;
+    let trilinear = error(&render(&shader, 32));
+    shader.sampler.max_anisotropy = 16.;
+    let anisotropic = error(&render(&shader, 32));
+    // Trilinear filtering blurs the stripes to gray in the distance.
+    assert!(anisotropic * 2. < trilinear, "anisotropic {} trilinear {}", anisotropic, trilinear);
+}
+
+#[test]
+fn texel_fetch_decodes_every_channel() {
+    use texture::{Texture, TextureFormat};
+
+    let mut texture = Texture::new(TextureFormat::RGB8, 2, 2).unwrap();
+    texture.pixels[9..12].copy_from_slice(&[51, 102, 255]);
+    assert_eq!(texture.texel_fetch(1, 1, 0), Vec4::new(0.2, 0.4, 1., 1.));
+
+    let mut texture = Texture::new(TextureFormat::RGBA8, 2, 1).unwrap();
+    texture.pixels.copy_from_slice(&[0, 0, 0, 0, 255, 0, 51, 102]);
+    assert_eq!(texture.texel_fetch(1, 0, 0), Vec4::new(1., 0., 0.2, 0.4));
+    texture.texel_store(0, 0, 0, Vec4::new(2., -1., 0.2, 0.4));
+    assert_eq!(texture.pixels[0..4], [255, 0, 51, 102]);
+
+    let mut texture = Texture::new(TextureFormat::R8, 3, 1).unwrap();
+    texture.pixels.copy_from_slice(&[0, 51, 255]);
+    assert_eq!(texture.texel_fetch(1, 0, 0), Vec4::new(0.2, 0.2, 0.2, 1.));
+    texture.generate_mipmaps().unwrap();
+    // (0 + 51) / 2 rounded to the nearest byte.
+    assert_eq!(texture.texel_fetch(0, 0, 1).x, 26. / 255.);
+}
+
+#[test]
+fn texel_round_trip_every_format() {
+    use texture::{Texture, TextureFormat};
+
+    let formats = [
+        TextureFormat::R8, TextureFormat::RG8, TextureFormat::RGB8, TextureFormat::SRGB8, TextureFormat::RGBA8,
+        TextureFormat::SRGB8_A8, TextureFormat::DEPTH_FLOAT, TextureFormat::STENCIL8,
+    ];
+    for format in formats {
+        let mut texture = Texture::new(format, 256, 1).unwrap();
+        let pixel_size = format.pixel_size();
+        match format {
+            TextureFormat::DEPTH_FLOAT => {
+                for x in 0..256 {
+                    texture.set_depth(x, 0, x as f32 / 255. - 0.5);
+                }
+            },
+            // Every byte value in every channel, with a different value per channel.
+            _ => {
+                for (i, byte) in texture.pixels.iter_mut().enumerate() {
+                    *byte = (i / pixel_size + (i % pixel_size) * 67) as u8;
+                }
+            },
+        }
+        let original = texture.pixels.clone();
+        for x in 0..256 {
+            let value = texture.texel_fetch(x, 0, 0);
+            texture.texel_store(x, 0, 0, value);
+        }
+        assert_eq!(texture.pixels, original, "{:?}", format);
+    }
+}
+
+#[test]
+fn half_float_conversion() {
+    use color::{f16_to_f32, f32_to_f16};
+
+    let cases = [
+        (0., 0x0000), (-0., 0x8000), (1., 0x3c00), (-2., 0xc000), (0.1, 0x2e66),
+        (65504., 0x7bff), (65520., 0x7c00), (f32::NEG_INFINITY, 0xfc00),
+        // Smallest subnormal, and values rounding to it or to zero.
+        (2f32.powi(-24), 0x0001), (2f32.powi(-25) * 1.5, 0x0001), (2f32.powi(-26), 0x0000),
+        // Halfway between 1 and the next half rounds to even.
+        (1. + 2f32.powi(-11), 0x3c00), (1. + 3. * 2f32.powi(-11), 0x3c02),
+    ];
+    for (value, bits) in cases {
+        assert_eq!(f32_to_f16(value), bits, "{}", value);
+    }
+    assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+    // Every half except NaNs survives a round trip through f32.
+    for bits in 0..=u16::MAX {
+        let value = f16_to_f32(bits);
+        if !value.is_nan() {
+            assert_eq!(f32_to_f16(value), bits);
+        }
+    }
+}
+
+#[test]
+fn float_formats_and_hdr_targets() {
+    use framebuffer::AttachmentType;
+    use render_state::{BlendComponent, BlendFactor, BlendState, RenderState};
+    use texture::{Texture, TextureFormat};
+
+    // Single-channel formats replicate red, two-channel formats leave blue at 0.
+    let value = Vec4::new(1000.5, -0.25, 3., 0.5);
+    let red = Vec4::new(1000.5, 1000.5, 1000.5, 1.);
+    let red_green = Vec4::new(1000.5, -0.25, 0., 1.);
+    let formats = [
+        (TextureFormat::R16F, 2, red), (TextureFormat::RG16F, 4, red_green), (TextureFormat::RGBA16F, 8, value),
+        (TextureFormat::R32F, 4, red), (TextureFormat::RG32F, 8, red_green), (TextureFormat::RGBA32F, 16, value),
+    ];
+    for (format, pixel_size, expected) in formats {
+        let mut texture = Texture::new(format, 2, 2).unwrap();
+        assert_eq!(texture.pixels.len(), 4 * pixel_size);
+        assert_eq!(texture.texel_fetch(1, 1, 0).x, 1.);
+        texture.texel_store(1, 1, 0, value);
+        assert_eq!(texture.texel_fetch(1, 1, 0), expected, "{:?}", format);
+    }
+    let mut texture = Texture::new(TextureFormat::RG8, 1, 1).unwrap();
+    texture.texel_store(0, 0, 0, Vec4::new(0.2, 2., 1., 0.));
+    assert_eq!(texture.texel_fetch(0, 0, 0), Vec4::new(0.2, 1., 0., 1.));
+
+    // Additive blending into float targets isn't clamped.
+    let additive = BlendComponent { src_factor: BlendFactor::One, dst_factor: BlendFactor::One, ..Default::default() };
+    let shader = SolidShader { color: Vec4::new(4., 0.5, 100., 1.) };
+    for format in [TextureFormat::RGBA16F, TextureFormat::RGBA32F] {
+        let mut framebuffer = framebuffer::FrameBuffer::new();
+        let texture = Texture::new(format, 2, 2).unwrap();
+        framebuffer.attach_texture(AttachmentType::Color(0), Some(Box::new(texture))).unwrap();
+        framebuffer.clear();
+        framebuffer.set_blend_state(BlendState { enabled: true, color: additive, alpha: additive, ..Default::default() });
+        for _ in 0..2 {
+            rasterizer::draw_arrays(&mut framebuffer, &RenderState::default(), &shader, &fullscreen_quad(0.));
+        }
+        let color = framebuffer.get_attachment(AttachmentType::Color(0)).unwrap();
+        assert_eq!(color.texel_fetch(1, 0, 0), Vec4::new(8., 1., 200., 2.));
+    }
+}
+
+#[test]
+fn texture_region_upload_and_readback() {
+    use crate::error::RenderError;
+    use texture::{Texture, TextureFormat};
+
+    let mut texture = Texture::new(TextureFormat::R8, 4, 3).unwrap();
+    // Partial uploads are rejected instead of being tiled over the texture.
+    assert_eq!(texture.set_texture_pixels(&[0; 4]), Err(RenderError::SizeMismatch { expected: 12, actual: 4 }));
+
+    // Rows of 2 texels padded to 3 bytes.
+    texture.update_region(1, 1, 2, 2, TextureFormat::R8, &[1, 2, 0, 3, 4], 3).unwrap();
+    assert_eq!(texture.pixels, [
+        255, 255, 255, 255,
+        255, 1, 2, 255,
+        255, 3, 4, 255,
+    ]);
+    let mut data = [0; 4];
+    texture.read_region(2, 1, 2, 2, TextureFormat::R8, &mut data, 2).unwrap();
+    assert_eq!(data, [2, 255, 4, 255]);
+
+    // Converted on the way in and out.
+    let mut texture = Texture::new(TextureFormat::RGBA32F, 2, 2).unwrap();
+    texture.update_region(0, 1, 2, 1, TextureFormat::RGBA8, &[255, 0, 51, 255, 0, 102, 0, 0], 8).unwrap();
+    assert_eq!(texture.texel_fetch(0, 1, 0), Vec4::new(1., 0., 0.2, 1.));
+    assert_eq!(texture.texel_fetch(1, 1, 0), Vec4::new(0., 0.4, 0., 0.));
+    texture.texel_store(0, 0, 0, Vec4::new(2., 0.5, -1., 1.));
+    let mut data = [0; 6];
+    texture.read_region(0, 0, 1, 2, TextureFormat::RGB8, &mut data, 3).unwrap();
+    assert_eq!(data, [255, 128, 0, 255, 0, 51]);
+
+    assert_eq!(
+        texture.update_region(1, 1, 2, 1, TextureFormat::RGBA8, &[0; 8], 8),
+        Err(RenderError::RegionOutOfBounds { x: 1, y: 1, width: 2, height: 1 }),
+    );
+    assert_eq!(
+        texture.read_region(0, 0, 2, 2, TextureFormat::RGBA8, &mut [0; 15], 8),
+        Err(RenderError::SizeMismatch { expected: 16, actual: 15 }),
+    );
+    assert!(texture.update_region(0, 0, 2, 1, TextureFormat::RGBA8, &[0; 8], 4).is_err());
+}
+
+#[test]
+fn cube_map_sampling_and_rendering() {
+    use crate::error::RenderError;
+    use framebuffer::AttachmentType;
+    use sampler::{Filter, MipmapFilter, Sampler, WrapMode};
+    use texture::TextureFormat;
+    use texture_cube::{CubeFace, TextureCube};
+
+    let colors = [
+        Vec4::new(1., 0., 0., 1.), Vec4::new(0., 1., 1., 1.), Vec4::new(0., 1., 0., 1.),
+        Vec4::new(1., 0., 1., 1.), Vec4::new(0., 0., 1., 1.), Vec4::new(1., 1., 0., 1.),
+    ];
+    let mut cube = TextureCube::new(TextureFormat::RGBA32F, 4).unwrap();
+    for (face, &color) in CubeFace::ALL.iter().zip(colors.iter()) {
+        let texture = cube.get_face_mut(*face);
+        for y in 0..4 {
+            for x in 0..4 {
+                texture.texel_store(x, y, 0, color);
+            }
+        }
+    }
+    let nearest = Sampler::new(Filter::Nearest, WrapMode::ClampToEdge);
+    let axes = [
+        Vec3::new(1., 0.2, 0.1), Vec3::new(-1., 0.2, 0.1), Vec3::new(0.2, 1., 0.1),
+        Vec3::new(0.2, -1., 0.1), Vec3::new(0.2, 0.1, 1.), Vec3::new(0.2, 0.1, -1.),
+    ];
+    for (&axis, &color) in axes.iter().zip(colors.iter()) {
+        assert_eq!(cube.sample(&nearest, axis), color);
+    }
+
+    // Face coordinates follow OpenGL: on +Z, u follows +x and v follows -y.
+    for face in CubeFace::ALL {
+        let texcoord = Vec2::new(0.3, 0.8);
+        assert_eq!(CubeFace::project(face.direction(texcoord) * 2.), (face, texcoord));
+    }
+    cube.get_face_mut(CubeFace::PositiveZ).texel_store(3, 0, 0, Vec4::new(1., 1., 1., 1.));
+    assert_eq!(cube.sample(&nearest, Vec3::new(0.9, 0.9, 1.)), Vec4::new(1., 1., 1., 1.));
+
+    // Bilinear footprints on the edge between +X and +Z blend both faces.
+    let linear = Sampler::new(Filter::Linear, WrapMode::ClampToEdge);
+    assert_eq!(cube.sample(&linear, Vec3::new(1., -0.1, 1.)), Vec4::new(0.5, 0., 0.5, 1.));
+
+    let mut trilinear = linear;
+    trilinear.mipmap_filter = MipmapFilter::Linear;
+    cube.generate_mipmaps().unwrap();
+    assert_eq!(cube.get_level_count(), 3);
+    assert_eq!(cube.sample_lod(&trilinear, Vec3::new(0., 0., -1.), 2.), colors[5]);
+
+    // Render to the -Y face through a framebuffer.
+    let mut framebuffer = framebuffer::FrameBuffer::new();
+    let face = cube.take_face(CubeFace::NegativeY).unwrap();
+    framebuffer.attach_texture(AttachmentType::Color(0), Some(face)).unwrap();
+    framebuffer.set_clear_color(0.25, 0.5, 0.75, 1.);
+    framebuffer.clear();
+    let face = framebuffer.detach_texture(AttachmentType::Color(0)).unwrap();
+    assert_eq!(framebuffer.get_width(), 0);
+    cube.put_face(CubeFace::NegativeY, face).unwrap();
+    assert_eq!(cube.sample(&nearest, Vec3::new(0., -1., 0.)), Vec4::new(0.25, 0.5, 0.75, 1.));
+
+    let wrong = texture::Texture::new(TextureFormat::RGBA8, 4, 4).unwrap();
+    assert_eq!(cube.put_face(CubeFace::NegativeY, Box::new(wrong)),
+        Err(RenderError::TextureFormatMismatch { expected: TextureFormat::RGBA32F, actual: TextureFormat::RGBA8 }));
+    let wrong = texture::Texture::new(TextureFormat::RGBA32F, 2, 4).unwrap();
+    assert_eq!(cube.put_face(CubeFace::NegativeY, Box::new(wrong)),
+        Err(RenderError::TextureSizeMismatch { expected: (4, 4), actual: (2, 4) }));
+}
+
+#[test]
+fn texture_arrays_and_volumes() {
+    use crate::error::RenderError;
+    use framebuffer::AttachmentType;
+    use sampler::{Filter, Sampler, WrapMode};
+    use texture::TextureFormat;
+    use texture_3d::Texture3D;
+    use texture_array::Texture2DArray;
+
+    let fill = |texture: &mut texture::Texture, value: f32| {
+        let (width, height) = texture.get_shape();
+        for y in 0..height {
+            for x in 0..width {
+                texture.texel_store(x, y, 0, Vec4::new(value, value, value, 1.));
+            }
+        }
+    };
+    let linear = Sampler::new(Filter::Linear, WrapMode::ClampToEdge);
+
+    // Layers are selected by rounding and never blended.
+    let mut array = Texture2DArray::new(TextureFormat::R32F, 2, 2, 3).unwrap();
+    for layer in 0..3 {
+        fill(array.get_layer_mut(layer), layer as f32);
+    }
+    assert_eq!(array.get_shape(), (2, 2, 3));
+    assert_eq!(array.sample(&linear, Vec3::new(0.5, 0.5, 1.4)).x, 1.);
+    assert_eq!(array.sample(&linear, Vec3::new(0.5, 0.5, 1.6)).x, 2.);
+    assert_eq!(array.sample(&linear, Vec3::new(0.5, 0.5, -3.)).x, 0.);
+    array.generate_mipmaps().unwrap();
+    assert_eq!(array.get_layer(2).get_level_count(), 2);
+
+    // Slices are blended, halfway between two slice centers.
+    let mut volume = Texture3D::new(TextureFormat::R32F, 2, 2, 4).unwrap();
+    for z in 0..4 {
+        fill(volume.get_slice_mut(z), z as f32);
+    }
+    volume.texel_store(1, 1, 3, Vec4::new(7., 7., 7., 1.));
+    assert_eq!(volume.texel_fetch(1, 1, 3).x, 7.);
+    assert_eq!(volume.sample(&linear, Vec3::new(0.25, 0.25, 0.5)).x, 1.5);
+    assert_eq!(volume.sample(&linear, Vec3::new(0.25, 0.25, 1.)).x, 3.);
+    let mut repeat = linear;
+    repeat.wrap_r = WrapMode::Repeat;
+    assert_eq!(volume.sample(&repeat, Vec3::new(0.25, 0.25, 0.)).x, 1.5);
+    let nearest = Sampler::new(Filter::Nearest, WrapMode::ClampToEdge);
+    assert_eq!(volume.sample(&nearest, Vec3::new(0.75, 0.75, 0.99)).x, 7.);
+
+    // Render into a layer and a slice.
+    let mut framebuffer = framebuffer::FrameBuffer::new();
+    framebuffer.set_clear_color(5., 5., 5., 1.);
+    let layer = array.take_layer(1).unwrap();
+    framebuffer.attach_texture(AttachmentType::Color(0), Some(layer)).unwrap();
+    let slice = volume.take_slice(2).unwrap();
+    framebuffer.attach_texture(AttachmentType::Color(1), Some(slice)).unwrap();
+    framebuffer.clear();
+    array.put_layer(1, framebuffer.detach_texture(AttachmentType::Color(0)).unwrap()).unwrap();
+    volume.put_slice(2, framebuffer.detach_texture(AttachmentType::Color(1)).unwrap()).unwrap();
+    assert_eq!(array.sample(&nearest, Vec3::new(0.5, 0.5, 1.)).x, 5.);
+    assert_eq!(volume.sample(&nearest, Vec3::new(0.5, 0.5, 0.6)).x, 5.);
+
+    assert_eq!(Texture2DArray::new(TextureFormat::R32F, 2, 2, 0).unwrap_err(), RenderError::InvalidLayerCount(0));
+    assert_eq!(Texture3D::new(TextureFormat::R32F, 2, 2, 0).unwrap_err(), RenderError::InvalidLayerCount(0));
+    let layer = texture::Texture::new(TextureFormat::R32F, 2, 2).unwrap();
+    assert_eq!(array.put_layer(5, Box::new(layer)), Err(RenderError::InvalidLayer { index: 5, count: 3 }));
+    let slice = texture::Texture::new(TextureFormat::R32F, 2, 2).unwrap();
+    assert_eq!(volume.put_slice(4, Box::new(slice)), Err(RenderError::InvalidLayer { index: 4, count: 4 }));
+    let wrong = texture::Texture::new(TextureFormat::R32F, 2, 1).unwrap();
+    assert_eq!(array.put_layer(0, Box::new(wrong)), Err(RenderError::TextureSizeMismatch { expected: (2, 2), actual: (2, 1) }));
+    let wrong = texture::Texture::new(TextureFormat::R8, 2, 2).unwrap();
+    assert_eq!(volume.put_slice(0, Box::new(wrong)),
+        Err(RenderError::TextureFormatMismatch { expected: TextureFormat::R32F, actual: TextureFormat::R8 }));
+}
+
+#[test]
+fn shadow_compare_sampling() {
+    use render_state::CompareFunc;
+    use sampler::{Filter, PcfKernel, Sampler, WrapMode};
+
+    // The left half of the shadow map is occluded at depth 0.3, the right half is empty.
+    let mut shadow_map = texture::Texture::new(texture::TextureFormat::DEPTH_FLOAT, 8, 8).unwrap();
+    for y in 0..8 {
+        for x in 0..4 {
+            shadow_map.set_depth(x, y, 0.3);
+        }
+    }
+    let mut sampler = Sampler::new(Filter::Nearest, WrapMode::ClampToEdge);
+    let edge = Vec2::new(0.5, 0.5);
+    let lit_center = Vec2::new(4.5 / 8., 0.5);
+    assert_eq!(sampler.sample_compare(&shadow_map, Vec2::new(0.4, 0.5), 0.5), 0.);
+    assert_eq!(sampler.sample_compare(&shadow_map, edge, 0.5), 1.);
+    assert_eq!(sampler.sample_compare(&shadow_map, Vec2::new(0.4, 0.5), 0.2), 1.);
+
+    sampler.mag_filter = Filter::Linear;
+    assert_eq!(sampler.sample_compare(&shadow_map, edge, 0.5), 0.5);
+    assert_eq!(sampler.sample_compare(&shadow_map, lit_center, 0.5), 1.);
+    sampler.pcf_kernel = PcfKernel::Box3x3;
+    assert_eq!(sampler.sample_compare(&shadow_map, edge, 0.5), 0.5);
+    assert!((sampler.sample_compare(&shadow_map, lit_center, 0.5) - 2. / 3.).abs() < 1e-6);
+    sampler.pcf_kernel = PcfKernel::Box5x5;
+    assert!((sampler.sample_compare(&shadow_map, lit_center, 0.5) - 3. / 5.).abs() < 1e-6);
+
+    sampler.pcf_kernel = PcfKernel::Poisson { radius: 1.5 };
+    let penumbra = sampler.sample_compare(&shadow_map, edge, 0.5);
+    assert!(penumbra > 0.2 && penumbra < 0.8);
+    assert_eq!(sampler.sample_compare(&shadow_map, Vec2::new(7. / 8., 0.5), 0.5), 1.);
+
+    // The comparison can be reversed, and texels outside compare against the border.
+    sampler.pcf_kernel = PcfKernel::Bilinear;
+    sampler.compare = CompareFunc::Greater;
+    assert_eq!(sampler.sample_compare(&shadow_map, Vec2::new(0.2, 0.5), 0.5), 1.);
+    sampler.wrap_s = WrapMode::ClampToBorder;
+    sampler.border_color = Vec4::new(1., 1., 1., 1.);
+    assert_eq!(sampler.sample_compare(&shadow_map, Vec2::new(-0.5, 0.5), 0.5), 0.);
+}
+
+#[test]
+fn block_compressed_formats() {
+    use crate::error::RenderError;
+    use texture::{Texture, TextureFormat};
+
+    // Decompress a single 4x4 block and return the first four texels of its bottom row.
+    fn decode(format: TextureFormat, block: &[u8]) -> Vec<u8> {
+        let mut texture = Texture::new(format, 4, 4).unwrap();
+        texture.set_texture_pixels(block).unwrap();
+        let texture = texture.decompress().unwrap();
+        let pixel_size = texture.format.pixel_size();
+        texture.pixels[..4 * pixel_size].to_vec()
+    }
+
+    // BC1: red and blue endpoints, indices 0, 1, 2, 3.
+    assert_eq!(decode(TextureFormat::BC1, &[0x00, 0xf8, 0x1f, 0x00, 0xe4, 0, 0, 0]),
+        [255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255, 85, 0, 170, 255]);
+    // Swapped endpoints select three colors and transparent black.
+    assert_eq!(decode(TextureFormat::BC1, &[0x1f, 0x00, 0x00, 0xf8, 0xe4, 0, 0, 0]),
+        [0, 0, 255, 255, 255, 0, 0, 255, 127, 0, 127, 255, 0, 0, 0, 0]);
+    // RGB565 channels replicate their high bits.
+    assert_eq!(decode(TextureFormat::BC1, &[0xef, 0x7b, 0, 0, 0, 0, 0, 0])[..4], [123, 125, 123, 255]);
+
+    // BC2: explicit 4-bit alpha, the color block always has four colors.
+    assert_eq!(decode(TextureFormat::BC2, &[0x8f, 0x50, 0, 0, 0, 0, 0, 0, 0x1f, 0x00, 0x00, 0xf8, 0xc0, 0, 0, 0]),
+        [0, 0, 255, 255, 0, 0, 255, 136, 0, 0, 255, 0, 170, 0, 85, 85]);
+    // BC3: interpolated alpha with eight values.
+    assert_eq!(decode(TextureFormat::BC3, &[255, 0, 0x88, 0x0e, 0, 0, 0, 0, 0xe0, 0x07, 0, 0, 0, 0, 0, 0]),
+        [0, 255, 0, 255, 0, 255, 0, 0, 0, 255, 0, 218, 0, 255, 0, 36]);
+    // BC4: six values plus 0 and 255 when the first endpoint is smaller.
+    assert_eq!(decode(TextureFormat::BC4, &[0, 255, 0xaa, 0x0f, 0, 0, 0, 0]), [51, 204, 0, 255]);
+    // BC5: two BC4 blocks for red and green.
+    assert_eq!(decode(TextureFormat::BC5, &[0, 255, 0xaa, 0x0f, 0, 0, 0, 0, 200, 100, 0, 0, 0, 0, 0, 0]),
+        [51, 200, 204, 200, 0, 200, 255, 200]);
+
+    // Partial blocks are padded, texels are fetched from the block holding them.
+    let mut texture = Texture::new(TextureFormat::BC4, 6, 5).unwrap();
+    assert_eq!(texture.pixels.len(), 4 * 8);
+    assert_eq!(texture.texel_fetch(5, 4, 0), Vec4::new(0., 0., 0., 1.));
+    let blocks: Vec<u8> = (0..4).flat_map(|i| [i * 50 + 50, 0, 0, 0, 0, 0, 0, 0]).collect();
+    texture.set_texture_pixels(&blocks).unwrap();
+    assert_eq!(texture.texel_fetch(3, 3, 0).x, 50. / 255.);
+    assert_eq!(texture.texel_fetch(4, 0, 0).x, 100. / 255.);
+    assert_eq!(texture.texel_fetch(5, 4, 0).x, 200. / 255.);
+    assert_eq!(texture.texture_sample(Vec2::new(0.1, 0.9)).x, 150. / 255.);
+
+    assert_eq!(texture.set_texture_pixels(&blocks[..8]),
+        Err(RenderError::SizeMismatch { expected: 32, actual: 8 }));
+    assert_eq!(texture.generate_mipmaps(), Err(RenderError::UnsupportedFormat(TextureFormat::BC4)));
+    assert_eq!(texture.update_region(0, 0, 4, 4, TextureFormat::R8, &[0; 16], 4),
+        Err(RenderError::UnsupportedFormat(TextureFormat::BC4)));
+    assert_eq!(Texture::new(TextureFormat::R8, 1, 1).unwrap().decompress().unwrap_err(),
+        RenderError::UnsupportedFormat(TextureFormat::R8));
+    let mut framebuffer = framebuffer::FrameBuffer::new();
+    assert!(framebuffer.attach_texture(framebuffer::AttachmentType::Color(0), Some(Box::new(texture))).is_err());
+}
@@ -1,147 +1,571 @@
-use std::convert::TryInto;
+use crate::math::preclude::{Vec2, Vec4, f32_clamp01, u32_max, u32_min};
+use crate::{vec4_one, vec4_zero};
 
-use crate::math::preclude::{Vec2, Vec4};
-use crate::math::utility::*;
-use crate::vec4_one;
+use crate::error::RenderError;
 
-use super::color::{u8_to_f32, convert_to_linear_color};
+use super::color::{u8_to_f32, f16_to_f32, f32_to_f16, convert_to_linear_color, convert_to_srgb_color};
+use super::sampler::Sampler;
 
-#[derive(PartialEq, Eq,Debug, Clone)]
+#[allow(non_camel_case_types)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum TextureFormat {
     R8,
+    RG8,
     RGB8,
     SRGB8,
     RGBA8,
     SRGB8_A8,
+    // Half-float formats.
+    R16F,
+    RG16F,
+    RGBA16F,
+    // Single precision float formats.
+    R32F,
+    RG32F,
+    RGBA32F,
     DEPTH_FLOAT,
+    STENCIL8,
+    // Block-compressed formats, 4x4 texels per block.
+    BC1,
+    BC2,
+    BC3,
+    BC4,
+    BC5,
 }
 
+// How a channel of a texel is stored.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Component {
+    // 8-bit normalized integer.
+    Unorm8,
+    Half,
+    Float,
+}
+
+impl Component {
+    #[inline]
+    fn size(&self) -> usize {
+        match self {
+            Component::Unorm8 => 1,
+            Component::Half => 2,
+            Component::Float => 4,
+        }
+    }
+
+    #[inline]
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        match self {
+            Component::Unorm8 => u8_to_f32(bytes[0]),
+            Component::Half => f16_to_f32(u16::from_ne_bytes([bytes[0], bytes[1]])),
+            Component::Float => f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+
+    #[inline]
+    fn encode(&self, value: f32, bytes: &mut [u8]) {
+        match self {
+            Component::Unorm8 => bytes[0] = unorm8(value),
+            Component::Half => bytes.copy_from_slice(&f32_to_f16(value).to_ne_bytes()),
+            Component::Float => bytes.copy_from_slice(&value.to_ne_bytes()),
+        }
+    }
+}
+
+impl TextureFormat {
+    // Number of channels of a texel and how they are stored.
+    #[inline]
+    fn layout(&self) -> (usize, Component) {
+        match self {
+            TextureFormat::R8 | TextureFormat::STENCIL8 => (1, Component::Unorm8),
+            TextureFormat::RG8 => (2, Component::Unorm8),
+            TextureFormat::RGB8 | TextureFormat::SRGB8 => (3, Component::Unorm8),
+            TextureFormat::RGBA8 | TextureFormat::SRGB8_A8 => (4, Component::Unorm8),
+            TextureFormat::R16F => (1, Component::Half),
+            TextureFormat::RG16F => (2, Component::Half),
+            TextureFormat::RGBA16F => (4, Component::Half),
+            TextureFormat::R32F | TextureFormat::DEPTH_FLOAT => (1, Component::Float),
+            TextureFormat::RG32F => (2, Component::Float),
+            TextureFormat::RGBA32F => (4, Component::Float),
+            _ => panic!("Block-compressed format {:?} has no texel layout.", self),
+        }
+    }
+
+    // Number of bytes of a texel, or of a 4x4 block for block-compressed formats.
+    #[inline]
+    pub fn pixel_size(&self) -> usize {
+        match self {
+            TextureFormat::BC1 | TextureFormat::BC4 => 8,
+            TextureFormat::BC2 | TextureFormat::BC3 | TextureFormat::BC5 => 16,
+            _ => {
+                let (count, component) = self.layout();
+                count * component.size()
+            },
+        }
+    }
+
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, TextureFormat::BC1 | TextureFormat::BC2 | TextureFormat::BC3
+            | TextureFormat::BC4 | TextureFormat::BC5)
+    }
+
+    // Number of bytes of a width x height image, partial blocks are padded to 4x4. None on overflow.
+    #[inline]
+    pub fn data_size(&self, width: u32, height: u32) -> Option<usize> {
+        let (columns, rows) = match self.is_compressed() {
+            true => (width.div_ceil(4), height.div_ceil(4)),
+            false => (width, height),
+        };
+        (columns as usize).checked_mul(rows as usize)?.checked_mul(self.pixel_size())
+    }
+
+    #[inline]
+    pub fn is_srgb(&self) -> bool {
+        matches!(self, TextureFormat::SRGB8 | TextureFormat::SRGB8_A8)
+    }
+
+    // Whether textures of the format can be attached as color attachments of a FrameBuffer.
+    #[inline]
+    pub fn is_color_renderable(&self) -> bool {
+        !matches!(self, TextureFormat::DEPTH_FLOAT | TextureFormat::STENCIL8) && !self.is_compressed()
+    }
+
+    /*
+     * Decode the bytes of a texel to RGBA. Missing channels are 0, or 1 for
+     * alpha. Single-channel formats (R8, R16F, R32F, DEPTH_FLOAT, STENCIL8)
+     * replicate their value to red, green and blue. sRGB colors are converted
+     * to linear space, stencil values are normalized to [0, 1]. Texels of
+     * block-compressed formats are decoded by Texture::texel_fetch() instead,
+     * these have no per-texel bytes.
+     */
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Vec4 {
+        let (count, component) = self.layout();
+        let size = component.size();
+        let mut channels = [0., 0., 0., 1.];
+        for (i, channel) in channels.iter_mut().enumerate().take(count) {
+            *channel = component.decode(&bytes[i * size..(i + 1) * size]);
+        }
+        if count == 1 {
+            channels[1] = channels[0];
+            channels[2] = channels[0];
+        }
+        if self.is_srgb() {
+            for channel in channels.iter_mut().take(3) {
+                *channel = convert_to_linear_color(*channel);
+            }
+        }
+        Vec4::from(&channels)
+    }
+
+    /*
+     * Encode RGBA to the bytes of a texel, the inverse of decode(). Normalized
+     * channels are clamped to [0, 1] and rounded to the nearest value, float
+     * channels are stored unclamped. Not for block-compressed formats.
+     */
+    pub(crate) fn encode(&self, value: Vec4, bytes: &mut [u8]) {
+        let (count, component) = self.layout();
+        let size = component.size();
+        let channels = [value.x, value.y, value.z, value.w];
+        for (i, &channel) in channels.iter().enumerate().take(count) {
+            let channel = match self.is_srgb() && i < 3 {
+                true => convert_to_srgb_color(f32_clamp01(channel)),
+                false => channel,
+            };
+            component.encode(channel, &mut bytes[i * size..(i + 1) * size]);
+        }
+    }
+}
+
+// Convert a value in [0, 1] to the nearest 8-bit normalized integer.
+#[inline]
+fn unorm8(value: f32) -> u8 {
+    (f32_clamp01(value) * 255. + 0.5) as u8
+}
+
+/*
+ * Decode texel `index` (x + 4 * y inside the block) of a block-compressed
+ * block. BC1-BC3 decode to RGBA, BC4 to red replicated like R8, BC5 to red
+ * and green like RG8.
+ */
+fn decode_block(format: TextureFormat, block: &[u8], index: usize) -> Vec4 {
+    let rgba = match format {
+        TextureFormat::BC1 => bc1_texel(block, index, true),
+        TextureFormat::BC2 => {
+            let alpha = (u64::from_le_bytes(block[..8].try_into().unwrap()) >> (4 * index)) & 0xf;
+            let [r, g, b, _] = bc1_texel(&block[8..], index, false);
+            [r, g, b, alpha as u8 * 17]
+        },
+        TextureFormat::BC3 => {
+            let [r, g, b, _] = bc1_texel(&block[8..], index, false);
+            [r, g, b, bc4_texel(block, index)]
+        },
+        TextureFormat::BC4 => {
+            let r = bc4_texel(block, index);
+            [r, r, r, 255]
+        },
+        TextureFormat::BC5 => [bc4_texel(block, index), bc4_texel(&block[8..], index), 0, 255],
+        _ => unreachable!(),
+    };
+    Vec4::new(u8_to_f32(rgba[0]), u8_to_f32(rgba[1]), u8_to_f32(rgba[2]), u8_to_f32(rgba[3]))
+}
+
+/*
+ * A texel of a BC1 color block: two RGB565 endpoints and 2-bit indices into
+ * a palette of four colors. If the first endpoint isn't greater than the
+ * second, the palette holds three colors and transparent black, unless the
+ * block is part of BC2/BC3 (`punch_through` is false).
+ */
+fn bc1_texel(block: &[u8], index: usize, punch_through: bool) -> [u8; 4] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let lerp = |a: u8, b: u8, wa: u32, wb: u32| ((a as u32 * wa + b as u32 * wb) / (wa + wb)) as u8;
+    let mix = |wa: u32, wb: u32| [lerp(e0[0], e1[0], wa, wb), lerp(e0[1], e1[1], wa, wb), lerp(e0[2], e1[2], wa, wb), 255];
+
+    match ((indices >> (2 * index)) & 3, c0 > c1 || !punch_through) {
+        (0, _) => [e0[0], e0[1], e0[2], 255],
+        (1, _) => [e1[0], e1[1], e1[2], 255],
+        (2, true) => mix(2, 1),
+        (3, true) => mix(1, 2),
+        (2, false) => mix(1, 1),
+        _ => [0, 0, 0, 0],
+    }
+}
+
+// Expand a RGB565 color to 8 bits per channel, replicating the high bits.
+#[inline]
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/*
+ * A texel of a BC4 block, also the alpha block of BC3 and the channels of
+ * BC5: two 8-bit endpoints and 3-bit indices. If the first endpoint is
+ * greater, six values are interpolated between them, otherwise four plus 0
+ * and 255.
+ */
+fn bc4_texel(block: &[u8], index: usize) -> u8 {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let code = ((u64::from_le_bytes(bits) >> (3 * index)) & 7) as u32;
+
+    match (code, a0 > a1) {
+        (0, _) => a0 as u8,
+        (1, _) => a1 as u8,
+        (code, true) => (((8 - code) * a0 + (code - 1) * a1) / 7) as u8,
+        (6, false) => 0,
+        (7, false) => 255,
+        (code, false) => (((6 - code) * a0 + (code - 1) * a1) / 5) as u8,
+    }
+}
+
+/*
+ * Largest width and height of a texture. The number of texels then fits in
+ * a u32, so texel indices can't overflow.
+ */
+pub const MAX_TEXTURE_SIZE: u32 = 32768;
+
 #[derive(Debug)]
 pub struct Texture {
     pub format: TextureFormat,
     pub width: u32,
     pub height: u32,
     pub pixels: Vec<u8>,
+    // Mip levels 1, 2, ... of the texture, each half the size of the previous one.
+    pub mipmaps: Vec<Texture>,
 }
 
 impl Texture {
-    pub fn new(format: TextureFormat, width: u32, height: u32) -> Option<Self> {
-        if width == 0 || height == 0 {
-            return None
+    pub fn new(format: TextureFormat, width: u32, height: u32) -> Result<Self, RenderError> {
+        if width == 0 || height == 0 || width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return Err(RenderError::InvalidDimensions { width, height });
         }
+        let size = format.data_size(width, height).ok_or(RenderError::InvalidDimensions { width, height })?;
 
-        let pixel_size = match format {
-            TextureFormat::R8 => 1,
-            TextureFormat::RGB8 | TextureFormat::SRGB8 => 3,
-            TextureFormat::RGBA8 | TextureFormat::SRGB8_A8 => 4,
-            TextureFormat::DEPTH_FLOAT => 4,
+        let pixels = match format {
+            // Stencils start at 0, compressed blocks of zeros decode to opaque black.
+            _ if format.is_compressed() || format == TextureFormat::STENCIL8 => vec![0; size],
+            // Colors start white and opaque, depths at the far plane.
+            _ => {
+                let mut texel = vec![0; format.pixel_size()];
+                format.encode(vec4_one!(), &mut texel);
+                texel.repeat(size / texel.len())
+            },
         };
 
-        Some(Self {
+        Ok(Self {
             format,
             width,
             height,
-            pixels: vec![255; (width * height * pixel_size) as usize],
+            pixels,
+            mipmaps: Vec::new(),
         })
     }
 
     pub fn get_texture_format(&self) -> TextureFormat {
-        self.format.clone()
+        self.format
     }
 
-    pub fn set_texture_pixels(&mut self, pixels: &[u8]) -> Result<(), &str>{
-        let pixel_size = self.get_pixel_size();
+    /*
+     * Replace the texels of the base level, `pixels` must hold exactly width *
+     * height texels, or the blocks covering them for block-compressed formats.
+     */
+    pub fn set_texture_pixels(&mut self, pixels: &[u8]) -> Result<(), RenderError> {
+        if pixels.len() != self.pixels.len() {
+            return Err(RenderError::SizeMismatch { expected: self.pixels.len(), actual: pixels.len() });
+        }
+        self.pixels.copy_from_slice(pixels);
+        Ok(())
+    }
 
-        match (self.height * self.width * pixel_size as u32) as usize >= pixels.len() {
-            true => {
-                // TODO: Optimize the efficient and decline RAM pressure.
-                for current_pixel in self.pixels.chunks_exact_mut(pixels.len()) {
-                    current_pixel.copy_from_slice(pixels);
-                }
+    /*
+     * Upload the rectangle of `width` x `height` texels starting at (x, y) of
+     * the base level. `data` holds the rows of the rectangle from bottom to
+     * top, `row_stride` bytes apart, with texels in `format`. Texels are
+     * converted to the format of the texture if the formats differ.
+     * Block-compressed textures can only be replaced as a whole, see
+     * set_texture_pixels().
+     */
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_region(&mut self, x: u32, y: u32, width: u32, height: u32,
+        format: TextureFormat, data: &[u8], row_stride: usize) -> Result<(), RenderError> {
+        self.check_region(x, y, width, height, format, data.len(), row_stride)?;
+        let src_size = format.pixel_size();
+        let dst_size = self.format.pixel_size();
+        for row in 0..height {
+            let src = &data[row as usize * row_stride..][..width as usize * src_size];
+            let offset = (x + (y + row) * self.width) as usize * dst_size;
+            let dst = &mut self.pixels[offset..offset + width as usize * dst_size];
+            match format == self.format {
+                true => dst.copy_from_slice(src),
+                false => {
+                    for (src, dst) in src.chunks_exact(src_size).zip(dst.chunks_exact_mut(dst_size)) {
+                        self.format.encode(format.decode(src), dst);
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
 
-                Ok(())
-            },
-            false => {
-                Err("[Error] Set texture failed.")
+    // Read back a rectangle of the base level into `data`, the inverse of update_region().
+    #[allow(clippy::too_many_arguments)]
+    pub fn read_region(&self, x: u32, y: u32, width: u32, height: u32,
+        format: TextureFormat, data: &mut [u8], row_stride: usize) -> Result<(), RenderError> {
+        self.check_region(x, y, width, height, format, data.len(), row_stride)?;
+        let src_size = self.format.pixel_size();
+        let dst_size = format.pixel_size();
+        for row in 0..height {
+            let offset = (x + (y + row) * self.width) as usize * src_size;
+            let src = &self.pixels[offset..offset + width as usize * src_size];
+            let dst = &mut data[row as usize * row_stride..][..width as usize * dst_size];
+            match format == self.format {
+                true => dst.copy_from_slice(src),
+                false => {
+                    for (src, dst) in src.chunks_exact(src_size).zip(dst.chunks_exact_mut(dst_size)) {
+                        format.encode(self.format.decode(src), dst);
+                    }
+                },
             }
         }
+        Ok(())
+    }
+
+    // Check that a region lies inside the texture and that a buffer of `length` bytes can hold it.
+    #[allow(clippy::too_many_arguments)]
+    fn check_region(&self, x: u32, y: u32, width: u32, height: u32,
+        format: TextureFormat, length: usize, row_stride: usize) -> Result<(), RenderError> {
+        for format in [format, self.format] {
+            if format.is_compressed() {
+                return Err(RenderError::UnsupportedFormat(format));
+            }
+        }
+        if x as u64 + width as u64 > self.width as u64 || y as u64 + height as u64 > self.height as u64 {
+            return Err(RenderError::RegionOutOfBounds { x, y, width, height });
+        }
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let row_size = width as usize * format.pixel_size();
+        if row_stride < row_size {
+            return Err(RenderError::SizeMismatch { expected: row_size, actual: row_stride });
+        }
+        let expected = row_stride * (height as usize - 1) + row_size;
+        if length < expected {
+            return Err(RenderError::SizeMismatch { expected, actual: length });
+        }
+        Ok(())
     }
 
     pub fn get_texture_pixels(&mut self) -> &mut Vec<u8> {
         &mut self.pixels
     }
 
+    /*
+     * Depth of the texel at (x, y) of a DEPTH_FLOAT texture. Every texel
+     * stores one f32 in native byte order.
+     */
+    #[inline]
+    pub fn get_depth(&self, x: u32, y: u32) -> f32 {
+        debug_assert!(self.format == TextureFormat::DEPTH_FLOAT);
+        let offset = ((x + y * self.width) * 4) as usize;
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.pixels[offset..offset + 4]);
+        f32::from_ne_bytes(bytes)
+    }
+
+    #[inline]
+    pub fn set_depth(&mut self, x: u32, y: u32, depth: f32) {
+        debug_assert!(self.format == TextureFormat::DEPTH_FLOAT);
+        let offset = ((x + y * self.width) * 4) as usize;
+        self.pixels[offset..offset + 4].copy_from_slice(&depth.to_ne_bytes());
+    }
+
+    // Stencil value of the texel at (x, y) of a STENCIL8 texture.
+    #[inline]
+    pub fn get_stencil(&self, x: u32, y: u32) -> u8 {
+        debug_assert!(self.format == TextureFormat::STENCIL8);
+        self.pixels[(x + y * self.width) as usize]
+    }
+
+    #[inline]
+    pub fn set_stencil(&mut self, x: u32, y: u32, stencil: u8) {
+        debug_assert!(self.format == TextureFormat::STENCIL8);
+        self.pixels[(x + y * self.width) as usize] = stencil;
+    }
+
     #[inline]
     pub fn get_pixel_size(&self) -> i32 {
-        let pixel_size = match self.format {
-            TextureFormat::R8 => 1,
-            TextureFormat::RGB8 | TextureFormat::SRGB8 => 3,
-            TextureFormat::RGBA8 | TextureFormat::SRGB8_A8 => 4,
-            TextureFormat::DEPTH_FLOAT => 4,
-        };
-        pixel_size
+        self.format.pixel_size() as i32
     }
 
     pub fn get_shape(&self) -> (u32, u32) {
         (self.width, self.height)
     }
 
-    pub fn texture_sample(&self, texcoord: Vec2) -> Vec4 {
-        let u = f32_clamp01(texcoord.x);
-        let v = f32_clamp01(texcoord.y);
-        let mut u_index = (u * self.width as f32) as u32;
-        let mut v_index = (v * self.height as f32) as u32;
-        if u_index >= self.width { u_index = self.width - 1 }
-        if v_index >= self.height { v_index = self.height - 1 }
-        let pixel_offset: usize = (u_index + v_index * self.width).try_into().unwrap();
-        let pixel_size = self.get_pixel_size();
-        /*
-         * pixel.x: red
-         * pixel.y: green
-         * pixel.z: blue
-         */
-        let mut pixel = vec4_one!();
-        match self.format {
-            TextureFormat::DEPTH_FLOAT => {
-                let target = self.pixels[pixel_offset];
-                pixel.x = target.into();
-                pixel.y = target.into();
-                pixel.z = target.into();
-           },
-            TextureFormat::R8 => {
-                let target = self.pixels[pixel_offset];
-                pixel.x = u8_to_f32(target);
-                pixel.y = pixel.x;
-                pixel.z = pixel.x;
-            },
-            _ => {
-                let target = self.pixels[pixel_offset * pixel_size as usize];
-                match pixel_size {
-                    1 => pixel.x = u8_to_f32(target),
-                    2 => {
-                        pixel.x = u8_to_f32(target);
-                        pixel.y = u8_to_f32(target+1);
-                    },
-                    3 => {
-                        pixel.x = u8_to_f32(target);
-                        pixel.y = u8_to_f32(target+1);
-                        pixel.z = u8_to_f32(target+2);
-                    },
-                    4 => {
-                        pixel.x = u8_to_f32(target);
-                        pixel.y = u8_to_f32(target+1);
-                        pixel.z = u8_to_f32(target+2);
-                        pixel.w = u8_to_f32(target+3);
+    // Number of mip levels, including the base level.
+    pub fn get_level_count(&self) -> usize {
+        self.mipmaps.len() + 1
+    }
+
+    // The texture of a mip level, level 0 is the texture itself.
+    pub fn get_level(&self, level: usize) -> &Texture {
+        match level {
+            0 => self,
+            _ => &self.mipmaps[level - 1],
+        }
+    }
+
+    /*
+     * Build the full mip chain down to 1x1 with a 2x2 box filter, replacing
+     * the existing levels. Colors of sRGB formats are averaged in linear
+     * space. Stencil and block-compressed textures can't be filtered, the
+     * levels of compressed textures are uploaded with the texture instead.
+     */
+    pub fn generate_mipmaps(&mut self) -> Result<(), RenderError> {
+        if self.format == TextureFormat::STENCIL8 || self.format.is_compressed() {
+            return Err(RenderError::UnsupportedFormat(self.format));
+        }
+        let mut levels = Vec::new();
+        let mut previous: &Texture = self;
+        while previous.width > 1 || previous.height > 1 {
+            levels.push(previous.downsample());
+            previous = levels.last().unwrap();
+        }
+        self.mipmaps = levels;
+        Ok(())
+    }
+
+    // The next mip level of the texture, see generate_mipmaps().
+    fn downsample(&self) -> Texture {
+        let width = u32_max(self.width / 2, 1);
+        let height = u32_max(self.height / 2, 1);
+        let mut level = Texture::new(self.format, width, height).unwrap();
+
+        for y in 0..height {
+            for x in 0..width {
+                // The 2x2 texels covered by the texel, clamped for sizes of 1.
+                let xs = [u32_min(2 * x, self.width - 1), u32_min(2 * x + 1, self.width - 1)];
+                let ys = [u32_min(2 * y, self.height - 1), u32_min(2 * y + 1, self.height - 1)];
+                // Decoded colors are linear, so sRGB textures are averaged in linear space.
+                let mut sum = vec4_zero!();
+                for &sy in ys.iter() {
+                    for &sx in xs.iter() {
+                        sum = sum + self.texel_fetch(sx, sy, 0);
                     }
-                    _ => panic!("Unsupported pixel type yet."),
                 }
-                if self.format == TextureFormat::SRGB8_A8 || self.format == TextureFormat::SRGB8 {
-                    pixel.x = convert_to_linear_color(pixel.x);
-                    pixel.y = convert_to_linear_color(pixel.y);
-                    pixel.z = convert_to_linear_color(pixel.z);
-                }
-            },
+                level.texel_store(x, y, 0, sum * 0.25);
+            }
+        }
+        level
+    }
+
+    // Sample with the default sampler: nearest filtering, clamped to the edges.
+    pub fn texture_sample(&self, texcoord: Vec2) -> Vec4 {
+        Sampler::default().sample(self, texcoord)
+    }
+
+    /*
+     * Decode the texel at integer coordinates (x, y) of a mip level to RGBA,
+     * see TextureFormat::decode(). Texels of block-compressed formats are
+     * decoded from the block holding them.
+     */
+    pub fn texel_fetch(&self, x: u32, y: u32, level: usize) -> Vec4 {
+        let texture = self.get_level(level);
+        let offset = texture.texel_offset(x, y);
+        let texel = &texture.pixels[offset..offset + texture.format.pixel_size()];
+        match texture.format.is_compressed() {
+            true => decode_block(texture.format, texel, (x % 4 + y % 4 * 4) as usize),
+            false => texture.format.decode(texel),
+        }
+    }
+
+    /*
+     * Decompress a block-compressed texture and its mip levels: BC1-BC3 to
+     * RGBA8, BC4 to R8 and BC5 to RG8.
+     */
+    pub fn decompress(&self) -> Result<Texture, RenderError> {
+        let format = match self.format {
+            TextureFormat::BC1 | TextureFormat::BC2 | TextureFormat::BC3 => TextureFormat::RGBA8,
+            TextureFormat::BC4 => TextureFormat::R8,
+            TextureFormat::BC5 => TextureFormat::RG8,
+            _ => return Err(RenderError::UnsupportedFormat(self.format)),
+        };
+        let mut texture = Texture::new(format, self.width, self.height)?;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                texture.texel_store(x, y, 0, self.texel_fetch(x, y, 0));
+            }
+        }
+        for level in self.mipmaps.iter() {
+            texture.mipmaps.push(level.decompress()?);
+        }
+        Ok(texture)
+    }
+
+    // Encode `value` into the texel at (x, y) of a mip level, see TextureFormat::encode().
+    pub fn texel_store(&mut self, x: u32, y: u32, level: usize, value: Vec4) {
+        let texture = match level {
+            0 => self,
+            _ => &mut self.mipmaps[level - 1],
+        };
+        assert!(!texture.format.is_compressed(), "Can't store texels of block-compressed format {:?}.", texture.format);
+        let offset = texture.texel_offset(x, y);
+        let pixel_size = texture.format.pixel_size();
+        texture.format.encode(value, &mut texture.pixels[offset..offset + pixel_size]);
+    }
+
+    #[inline]
+    fn texel_offset(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Texel ({}, {}) is out of the texture.", x, y);
+        match self.format.is_compressed() {
+            true => (x / 4 + y / 4 * self.width.div_ceil(4)) as usize * self.format.pixel_size(),
+            false => (x + y * self.width) as usize * self.format.pixel_size(),
         }
-        pixel
     }
 }
@@ -0,0 +1,89 @@
+use crate::error::RenderError;
+use crate::math::preclude::{Vec3, Vec4};
+
+use super::sampler::{Filter, Sampler};
+use super::texture::{Texture, TextureFormat};
+use super::texture_array::Layers;
+
+/*
+ * A volume of texels stored as `depth` slices of width x height, sampled with
+ * normalized (u, v, w) coordinates. Only the base level is supported.
+ */
+#[derive(Debug)]
+pub struct Texture3D {
+    slices: Layers,
+}
+
+impl Texture3D {
+    pub fn new(format: TextureFormat, width: u32, height: u32, depth: u32) -> Result<Self, RenderError> {
+        Ok(Self { slices: Layers::new(format, width, height, depth)? })
+    }
+
+    pub fn get_texture_format(&self) -> TextureFormat {
+        self.slices.format
+    }
+
+    // (width, height, depth)
+    pub fn get_shape(&self) -> (u32, u32, u32) {
+        (self.slices.width, self.slices.height, self.slices.len() as u32)
+    }
+
+    // Panics if the slice has been taken out.
+    pub fn get_slice(&self, slice: usize) -> &Texture {
+        self.slices.get(slice)
+    }
+
+    pub fn get_slice_mut(&mut self, slice: usize) -> &mut Texture {
+        self.slices.get_mut(slice)
+    }
+
+    pub fn texel_fetch(&self, x: u32, y: u32, z: u32) -> Vec4 {
+        self.slices.get(z as usize).texel_fetch(x, y, 0)
+    }
+
+    pub fn texel_store(&mut self, x: u32, y: u32, z: u32, value: Vec4) {
+        self.slices.get_mut(z as usize).texel_store(x, y, 0, value);
+    }
+
+    // Take a slice out of the volume, e.g. to attach it to a FrameBuffer.
+    pub fn take_slice(&mut self, slice: usize) -> Option<Box<Texture>> {
+        self.slices.take(slice)
+    }
+
+    // Put back a slice, which must match the format and size of the volume.
+    pub fn put_slice(&mut self, slice: usize, texture: Box<Texture>) -> Result<(), RenderError> {
+        self.slices.put(slice, texture)
+    }
+
+    /*
+     * Sample the volume at `texcoord`. With Filter::Linear the eight closest
+     * texels are blended (trilinear filtering), w wraps with sampler.wrap_r.
+     */
+    pub fn sample(&self, sampler: &Sampler, texcoord: Vec3) -> Vec4 {
+        self.sample_lod(sampler, texcoord, 0.)
+    }
+
+    // Sample with an explicit LOD, which only selects between the min and mag filters.
+    pub fn sample_lod(&self, sampler: &Sampler, texcoord: Vec3, lod: f32) -> Vec4 {
+        sampler.filter_levels(1, lod, |_, filter| self.filter(sampler, texcoord, filter))
+    }
+
+    fn filter(&self, sampler: &Sampler, texcoord: Vec3, filter: Filter) -> Vec4 {
+        let depth = self.slices.len() as u32;
+        let uv = texcoord.into_vec2();
+        let z = texcoord.z * depth as f32;
+        let slice = |index: i64| match sampler.wrap_r.apply(index, depth) {
+            Some(index) => sampler.filter(self.slices.get(index as usize), uv, filter),
+            None => sampler.border_color,
+        };
+        match filter {
+            Filter::Nearest => slice(z.floor() as i64),
+            Filter::Linear => {
+                let z = z - 0.5;
+                let z0 = z.floor();
+                let t = z - z0;
+                slice(z0 as i64) * (1. - t) + slice(z0 as i64 + 1) * t
+            },
+        }
+    }
+}
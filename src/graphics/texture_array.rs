@@ -0,0 +1,130 @@
+use crate::error::RenderError;
+use crate::math::preclude::{Vec3, Vec4};
+
+use super::sampler::Sampler;
+use super::texture::{Texture, TextureFormat};
+
+/*
+ * A stack of 2D textures of the same format and size, the storage of the
+ * layers of Texture2DArray and the slices of Texture3D. A layer can be taken
+ * out to be attached to a FrameBuffer, and must be put back before sampling.
+ */
+#[derive(Debug)]
+pub(crate) struct Layers {
+    pub(crate) format: TextureFormat,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    textures: Vec<Option<Box<Texture>>>,
+}
+
+impl Layers {
+    pub(crate) fn new(format: TextureFormat, width: u32, height: u32, count: u32) -> Result<Self, RenderError> {
+        if count == 0 {
+            return Err(RenderError::InvalidLayerCount(count));
+        }
+        let mut textures = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            textures.push(Some(Box::new(Texture::new(format, width, height)?)));
+        }
+        Ok(Self { format, width, height, textures })
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub(crate) fn get(&self, index: usize) -> &Texture {
+        match self.textures[index] {
+            Some(ref texture) => texture,
+            None => panic!("The layer {} has been taken out.", index),
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> &mut Texture {
+        match self.textures[index] {
+            Some(ref mut texture) => texture,
+            None => panic!("The layer {} has been taken out.", index),
+        }
+    }
+
+    pub(crate) fn take(&mut self, index: usize) -> Option<Box<Texture>> {
+        self.textures.get_mut(index)?.take()
+    }
+
+    pub(crate) fn put(&mut self, index: usize, texture: Box<Texture>) -> Result<(), RenderError> {
+        let count = self.len();
+        let slot = self.textures.get_mut(index).ok_or(RenderError::InvalidLayer { index, count })?;
+        if texture.format != self.format {
+            return Err(RenderError::TextureFormatMismatch { expected: self.format, actual: texture.format });
+        }
+        if texture.width != self.width || texture.height != self.height {
+            let (expected, actual) = ((self.width, self.height), texture.get_shape());
+            return Err(RenderError::TextureSizeMismatch { expected, actual });
+        }
+        *slot = Some(texture);
+        Ok(())
+    }
+}
+
+// 2D textures indexed by layer, sampled with (u, v, layer).
+#[derive(Debug)]
+pub struct Texture2DArray {
+    layers: Layers,
+}
+
+impl Texture2DArray {
+    pub fn new(format: TextureFormat, width: u32, height: u32, layers: u32) -> Result<Self, RenderError> {
+        Ok(Self { layers: Layers::new(format, width, height, layers)? })
+    }
+
+    pub fn get_texture_format(&self) -> TextureFormat {
+        self.layers.format
+    }
+
+    // (width, height, layers)
+    pub fn get_shape(&self) -> (u32, u32, u32) {
+        (self.layers.width, self.layers.height, self.layers.len() as u32)
+    }
+
+    // Panics if the layer has been taken out.
+    pub fn get_layer(&self, layer: usize) -> &Texture {
+        self.layers.get(layer)
+    }
+
+    pub fn get_layer_mut(&mut self, layer: usize) -> &mut Texture {
+        self.layers.get_mut(layer)
+    }
+
+    // Take a layer out of the array, e.g. to attach it to a FrameBuffer.
+    pub fn take_layer(&mut self, layer: usize) -> Option<Box<Texture>> {
+        self.layers.take(layer)
+    }
+
+    // Put back a layer, which must match the format and size of the array.
+    pub fn put_layer(&mut self, layer: usize, texture: Box<Texture>) -> Result<(), RenderError> {
+        self.layers.put(layer, texture)
+    }
+
+    // Generate the mip chain of every layer, see Texture::generate_mipmaps().
+    pub fn generate_mipmaps(&mut self) -> Result<(), RenderError> {
+        for layer in 0..self.layers.len() {
+            self.layers.get_mut(layer).generate_mipmaps()?;
+        }
+        Ok(())
+    }
+
+    // Sample at the base level, see Texture2DArray::sample_lod().
+    pub fn sample(&self, sampler: &Sampler, texcoord: Vec3) -> Vec4 {
+        self.sample_lod(sampler, texcoord, 0.)
+    }
+
+    /*
+     * Sample the layer texcoord.z, rounded to the nearest layer and clamped to
+     * the array, at (texcoord.x, texcoord.y). Layers are never blended.
+     */
+    pub fn sample_lod(&self, sampler: &Sampler, texcoord: Vec3, lod: f32) -> Vec4 {
+        let layer = (texcoord.z.round().max(0.) as usize).min(self.layers.len() - 1);
+        sampler.sample_lod(self.layers.get(layer), texcoord.into_vec2(), lod)
+    }
+}
@@ -0,0 +1,196 @@
+use crate::error::RenderError;
+use crate::math::preclude::{Vec2, Vec3, Vec4};
+
+use super::sampler::{Filter, Sampler};
+use super::texture::{Texture, TextureFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    #[inline]
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /*
+     * Project a direction onto the face of its major axis, returning the face
+     * and the texture coordinates in [0, 1] on it. Faces are laid out as in
+     * OpenGL, v grows downwards on the side faces.
+     */
+    pub fn project(direction: Vec3) -> (CubeFace, Vec2) {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+            match x >= 0. {
+                true => (CubeFace::PositiveX, -z, -y, ax),
+                false => (CubeFace::NegativeX, z, -y, ax),
+            }
+        } else if ay >= az {
+            match y >= 0. {
+                true => (CubeFace::PositiveY, x, z, ay),
+                false => (CubeFace::NegativeY, x, -z, ay),
+            }
+        } else {
+            match z >= 0. {
+                true => (CubeFace::PositiveZ, x, -y, az),
+                false => (CubeFace::NegativeZ, -x, -y, az),
+            }
+        };
+        (face, Vec2::new((sc / ma + 1.) * 0.5, (tc / ma + 1.) * 0.5))
+    }
+
+    // The direction of the point at `texcoord` on the face, the inverse of project().
+    pub fn direction(&self, texcoord: Vec2) -> Vec3 {
+        let sc = texcoord.x * 2. - 1.;
+        let tc = texcoord.y * 2. - 1.;
+        match self {
+            CubeFace::PositiveX => Vec3::new(1., -tc, -sc),
+            CubeFace::NegativeX => Vec3::new(-1., -tc, sc),
+            CubeFace::PositiveY => Vec3::new(sc, 1., tc),
+            CubeFace::NegativeY => Vec3::new(sc, -1., -tc),
+            CubeFace::PositiveZ => Vec3::new(sc, -tc, 1.),
+            CubeFace::NegativeZ => Vec3::new(-sc, -tc, -1.),
+        }
+    }
+}
+
+/*
+ * Six square faces of the same size and format, sampled by direction. A face
+ * can be taken out with take_face() to attach it to a FrameBuffer, and must
+ * be put back with put_face() before sampling.
+ */
+#[derive(Debug)]
+pub struct TextureCube {
+    format: TextureFormat,
+    size: u32,
+    faces: [Option<Box<Texture>>; 6],
+}
+
+impl TextureCube {
+    pub fn new(format: TextureFormat, size: u32) -> Result<Self, RenderError> {
+        let mut faces: [Option<Box<Texture>>; 6] = Default::default();
+        for face in faces.iter_mut() {
+            *face = Some(Box::new(Texture::new(format, size, size)?));
+        }
+        Ok(Self { format, size, faces })
+    }
+
+    pub fn get_texture_format(&self) -> TextureFormat {
+        self.format
+    }
+
+    // Width and height of the faces.
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    // Panics if the face has been taken out.
+    pub fn get_face(&self, face: CubeFace) -> &Texture {
+        match self.faces[face.index()] {
+            Some(ref texture) => texture,
+            None => panic!("The face {:?} has been taken out of the cube.", face),
+        }
+    }
+
+    pub fn get_face_mut(&mut self, face: CubeFace) -> &mut Texture {
+        match self.faces[face.index()] {
+            Some(ref mut texture) => texture,
+            None => panic!("The face {:?} has been taken out of the cube.", face),
+        }
+    }
+
+    // Take a face out of the cube, e.g. to attach it to a FrameBuffer for render-to-cubemap.
+    pub fn take_face(&mut self, face: CubeFace) -> Option<Box<Texture>> {
+        self.faces[face.index()].take()
+    }
+
+    // Put back a face, which must match the format and size of the cube.
+    pub fn put_face(&mut self, face: CubeFace, texture: Box<Texture>) -> Result<(), RenderError> {
+        if texture.format != self.format {
+            return Err(RenderError::TextureFormatMismatch { expected: self.format, actual: texture.format });
+        }
+        if texture.width != self.size || texture.height != self.size {
+            let (expected, actual) = ((self.size, self.size), texture.get_shape());
+            return Err(RenderError::TextureSizeMismatch { expected, actual });
+        }
+        self.faces[face.index()] = Some(texture);
+        Ok(())
+    }
+
+    // Generate the mip chain of every face, see Texture::generate_mipmaps().
+    pub fn generate_mipmaps(&mut self) -> Result<(), RenderError> {
+        for face in CubeFace::ALL {
+            self.get_face_mut(face).generate_mipmaps()?;
+        }
+        Ok(())
+    }
+
+    pub fn get_level_count(&self) -> usize {
+        self.get_face(CubeFace::PositiveX).get_level_count()
+    }
+
+    // Sample in `direction` at the base level, see Sampler::sample().
+    pub fn sample(&self, sampler: &Sampler, direction: Vec3) -> Vec4 {
+        self.sample_lod(sampler, direction, 0.)
+    }
+
+    /*
+     * Sample in `direction` at an explicit level of detail. Wrap modes of the
+     * sampler are ignored: bilinear footprints crossing an edge of a face
+     * read the texels of the adjacent face, so filtering is seamless.
+     */
+    pub fn sample_lod(&self, sampler: &Sampler, direction: Vec3, lod: f32) -> Vec4 {
+        let (face, texcoord) = CubeFace::project(direction);
+        sampler.filter_levels(self.get_level_count(), lod, |level, filter| {
+            self.filter(face, texcoord, level, filter)
+        })
+    }
+
+    fn filter(&self, face: CubeFace, texcoord: Vec2, level: usize, filter: Filter) -> Vec4 {
+        let size = self.get_face(face).get_level(level).width as i64;
+        let x = texcoord.x * size as f32;
+        let y = texcoord.y * size as f32;
+        match filter {
+            Filter::Nearest => self.fetch(face, x.floor() as i64, y.floor() as i64, level),
+            Filter::Linear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let bottom = self.fetch(face, x0, y0, level) * (1. - tx) + self.fetch(face, x0 + 1, y0, level) * tx;
+                let top = self.fetch(face, x0, y0 + 1, level) * (1. - tx) + self.fetch(face, x0 + 1, y0 + 1, level) * tx;
+                bottom * (1. - ty) + top * ty
+            },
+        }
+    }
+
+    // Fetch a texel of a face, texels beyond its edges are re-projected onto the adjacent faces.
+    fn fetch(&self, face: CubeFace, x: i64, y: i64, level: usize) -> Vec4 {
+        let size = self.get_face(face).get_level(level).width as i64;
+        if (0..size).contains(&x) && (0..size).contains(&y) {
+            return self.get_face(face).texel_fetch(x as u32, y as u32, level);
+        }
+        let center = Vec2::new((x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32);
+        let (face, texcoord) = CubeFace::project(face.direction(center));
+        let x = ((texcoord.x * size as f32) as i64).clamp(0, size - 1);
+        let y = ((texcoord.y * size as f32) as i64).clamp(0, size - 1);
+        self.get_face(face).texel_fetch(x as u32, y as u32, level)
+    }
+}
@@ -0,0 +1,5 @@
+pub mod tga;
+
+
+#[cfg(test)]
+mod test;
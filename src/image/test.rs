@@ -0,0 +1,152 @@
+use crate::error::{ImageError, RenderError};
+use crate::graphics::framebuffer::{AttachmentType, FrameBuffer};
+use crate::graphics::texture::{Texture, TextureFormat};
+
+use super::tga;
+
+
+// A TGA header without image ID.
+fn tga_header(color_map: (u8, u16, u8), image_type: u8, width: u16, height: u16, depth: u8, descriptor: u8) -> Vec<u8> {
+    let (color_map_type, length, entry_depth) = color_map;
+    let mut header = vec![0, color_map_type, image_type, 0, 0];
+    header.extend_from_slice(&length.to_le_bytes());
+    header.extend_from_slice(&[entry_depth, 0, 0, 0, 0]);
+    header.extend_from_slice(&width.to_le_bytes());
+    header.extend_from_slice(&height.to_le_bytes());
+    header.extend_from_slice(&[depth, descriptor]);
+    header
+}
+
+#[test]
+fn tga_decode_true_color_orientations() {
+    // Red and green at the bottom, blue and white at the top.
+    let expected = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+    let bottom = [0, 0, 255, 0, 255, 0];
+    let top = [255, 0, 0, 255, 255, 255];
+
+    let mut data = tga_header((0, 0, 0), 2, 2, 2, 24, 0);
+    data.extend_from_slice(&bottom);
+    data.extend_from_slice(&top);
+    let texture = tga::decode(&data, false).unwrap();
+    assert_eq!((texture.format, texture.get_shape()), (TextureFormat::RGB8, (2, 2)));
+    assert_eq!(texture.pixels, expected);
+
+    let mut data = tga_header((0, 0, 0), 2, 2, 2, 24, 0x20);
+    data.extend_from_slice(&top);
+    data.extend_from_slice(&bottom);
+    assert_eq!(tga::decode(&data, false).unwrap().pixels, expected);
+
+    // Top to bottom and right to left.
+    let mut data = tga_header((0, 0, 0), 2, 2, 2, 24, 0x30);
+    data.extend_from_slice(&[255, 255, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255]);
+    let texture = tga::decode(&data, true).unwrap();
+    assert_eq!(texture.format, TextureFormat::SRGB8);
+    assert_eq!(texture.pixels, expected);
+
+    // 16-bit pixels with an alpha bit.
+    let mut data = tga_header((0, 0, 0), 2, 2, 1, 16, 1);
+    data.extend_from_slice(&[0x00, 0xfc, 0x1f, 0x00]);
+    let texture = tga::decode(&data, false).unwrap();
+    assert_eq!(texture.format, TextureFormat::RGBA8);
+    assert_eq!(texture.pixels, [255, 0, 0, 255, 0, 0, 255, 0]);
+}
+
+#[test]
+fn tga_decode_rle_and_color_mapped() {
+    // A run of two pixels and a raw pixel.
+    let mut data = tga_header((0, 0, 0), 10, 3, 1, 32, 8);
+    data.extend_from_slice(&[0x81, 10, 20, 30, 40, 0x00, 1, 2, 3, 4]);
+    let texture = tga::decode(&data, true).unwrap();
+    assert_eq!(texture.format, TextureFormat::SRGB8_A8);
+    assert_eq!(texture.pixels, [30, 20, 10, 40, 30, 20, 10, 40, 3, 2, 1, 4]);
+
+    // Without alpha bits in the descriptor, the fourth byte is ignored.
+    let mut data = tga_header((0, 0, 0), 2, 2, 1, 32, 0);
+    data.extend_from_slice(&[10, 20, 30, 0, 1, 2, 3, 77]);
+    let texture = tga::decode(&data, false).unwrap();
+    assert_eq!(texture.format, TextureFormat::RGB8);
+    assert_eq!(texture.pixels, [30, 20, 10, 3, 2, 1]);
+
+    // Packets may cross rows.
+    let mut data = tga_header((0, 0, 0), 11, 2, 2, 8, 0);
+    data.extend_from_slice(&[0x82, 7, 0x00, 9]);
+    let texture = tga::decode(&data, false).unwrap();
+    assert_eq!(texture.format, TextureFormat::R8);
+    assert_eq!(texture.pixels, [7, 7, 7, 9]);
+
+    // Indices into a map of red and blue.
+    let color_map = [0, 0, 255, 255, 0, 0];
+    let mut data = tga_header((1, 2, 24), 1, 2, 1, 8, 0);
+    data.extend_from_slice(&color_map);
+    data.extend_from_slice(&[1, 0]);
+    assert_eq!(tga::decode(&data, false).unwrap().pixels, [0, 0, 255, 255, 0, 0]);
+
+    let mut data = tga_header((1, 2, 24), 9, 2, 1, 8, 0);
+    data.extend_from_slice(&color_map);
+    data.extend_from_slice(&[0x81, 5]);
+    assert_eq!(tga::decode(&data, false).unwrap_err(), ImageError::InvalidColorIndex(5));
+
+    // Malformed and unsupported images.
+    assert_eq!(tga::decode(&data[..10], false).unwrap_err(), ImageError::Truncated);
+    let mut data = tga_header((0, 0, 0), 2, 2, 2, 24, 0);
+    data[0] = 200;
+    assert_eq!(tga::decode(&data, false).unwrap_err(), ImageError::Truncated);
+    let mut data = tga_header((0, 0, 0), 10, 32768, 32768, 32, 8);
+    data.extend_from_slice(&[0xff, 1, 2, 3, 4]);
+    assert_eq!(tga::decode(&data, false).unwrap_err(), ImageError::Truncated);
+    let mut data = tga_header((0, 0, 0), 2, 2, 2, 24, 0);
+    data.extend_from_slice(&[0; 9]);
+    assert_eq!(tga::decode(&data, false).unwrap_err(), ImageError::Truncated);
+    let data = tga_header((0, 0, 0), 32, 2, 2, 24, 0);
+    assert_eq!(tga::decode(&data, false).unwrap_err(), ImageError::UnsupportedImageType(32));
+    let data = tga_header((0, 0, 0), 3, 2, 2, 16, 0);
+    assert_eq!(tga::decode(&data, false).unwrap_err(), ImageError::UnsupportedPixelDepth(16));
+    let data = tga_header((0, 0, 0), 2, 0, 2, 24, 0);
+    assert_eq!(tga::decode(&data, false).unwrap_err(),
+        ImageError::Texture(RenderError::InvalidDimensions { width: 0, height: 2 }));
+}
+
+#[test]
+fn tga_encode_round_trip() {
+    let mut texture = Texture::new(TextureFormat::RGBA8, 37, 5).unwrap();
+    for (i, byte) in texture.pixels.iter_mut().enumerate() {
+        // Runs of repeated pixels mixed with distinct ones.
+        *byte = match (i / 4) % 7 < 3 {
+            true => 200,
+            false => (i * 13) as u8,
+        };
+    }
+    let raw = tga::encode(&texture, false).unwrap();
+    let rle = tga::encode(&texture, true).unwrap();
+    assert!(rle.len() < raw.len());
+    assert!(raw.ends_with(b"TRUEVISION-XFILE.\0"));
+    for data in [raw, rle] {
+        let decoded = tga::decode(&data, false).unwrap();
+        assert_eq!((decoded.format, decoded.get_shape()), (TextureFormat::RGBA8, (37, 5)));
+        assert_eq!(decoded.pixels, texture.pixels);
+    }
+
+    // Float textures are converted, single-channel ones become grayscale.
+    let mut depth = Texture::new(TextureFormat::DEPTH_FLOAT, 2, 1).unwrap();
+    depth.set_depth(0, 0, 0.5);
+    let decoded = tga::decode(&tga::encode(&depth, true).unwrap(), false).unwrap();
+    assert_eq!((decoded.format, decoded.pixels), (TextureFormat::R8, vec![128, 255]));
+
+    // Color attachments of a framebuffer.
+    let mut framebuffer = FrameBuffer::new();
+    let color = Texture::new(TextureFormat::SRGB8, 4, 4).unwrap();
+    framebuffer.attach_texture(AttachmentType::Color(0), Some(Box::new(color))).unwrap();
+    framebuffer.set_clear_color(1., 0., 0., 1.);
+    framebuffer.clear_color();
+    let data = tga::encode_attachment(&framebuffer, AttachmentType::Color(0), true).unwrap();
+    let decoded = tga::decode(&data, true).unwrap();
+    assert_eq!(decoded.format, TextureFormat::SRGB8);
+    assert_eq!(&decoded.pixels, &framebuffer.get_attachment(AttachmentType::Color(0)).unwrap().pixels);
+    assert_eq!(&decoded.pixels[..3], &[255, 0, 0]);
+    assert_eq!(tga::encode_attachment(&framebuffer, AttachmentType::Color(1), false).unwrap_err(),
+        ImageError::Texture(RenderError::MissingAttachment(AttachmentType::Color(1))));
+    assert_eq!(tga::encode_attachment(&framebuffer, AttachmentType::Stencil, false).unwrap_err(),
+        ImageError::Texture(RenderError::MissingAttachment(AttachmentType::Stencil)));
+    assert_eq!(tga::encode_attachment(&framebuffer, AttachmentType::Color(8), false).unwrap_err(),
+        ImageError::Texture(RenderError::InvalidAttachment(AttachmentType::Color(8))));
+}
@@ -0,0 +1,333 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{ImageError, RenderError};
+use crate::graphics::framebuffer::{AttachmentType, FrameBuffer, MAX_COLOR_ATTACHMENTS};
+use crate::graphics::texture::{Texture, TextureFormat};
+use crate::math::preclude::f32_clamp01;
+
+const HEADER_SIZE: usize = 18;
+const FOOTER_SIGNATURE: &[u8] = b"TRUEVISION-XFILE.\0";
+
+// Image types of the header, RLE compressed types add 8.
+const COLOR_MAPPED: u8 = 1;
+const TRUE_COLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+const RLE: u8 = 8;
+
+// Bits of the image descriptor.
+const ALPHA_BITS: u8 = 0x0f;
+const RIGHT_TO_LEFT: u8 = 0x10;
+const TOP_TO_BOTTOM: u8 = 0x20;
+
+struct Header {
+    id_length: usize,
+    color_map_type: u8,
+    image_type: u8,
+    color_map_first: usize,
+    color_map_length: usize,
+    color_map_depth: u8,
+    width: u32,
+    height: u32,
+    pixel_depth: u8,
+    descriptor: u8,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, ImageError> {
+        if data.len() < HEADER_SIZE {
+            return Err(ImageError::Truncated);
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        Ok(Self {
+            id_length: data[0] as usize,
+            color_map_type: data[1],
+            image_type: data[2],
+            color_map_first: u16_at(3) as usize,
+            color_map_length: u16_at(5) as usize,
+            color_map_depth: data[7],
+            width: u16_at(12) as u32,
+            height: u16_at(14) as u32,
+            pixel_depth: data[16],
+            descriptor: data[17],
+        })
+    }
+}
+
+/*
+ * Decode a TGA image to a texture. Uncompressed and RLE compressed
+ * true-color (15, 16, 24 and 32 bits), grayscale (8 bits) and color-mapped
+ * images are supported. Grayscale images decode to R8, others to RGB8, or
+ * RGBA8 if the descriptor declares alpha bits. With `srgb` the colors are treated as
+ * sRGB encoded, giving SRGB8 and SRGB8_A8 textures. The rows of the texture
+ * go from bottom to top, whatever the origin of the image.
+ */
+pub fn decode(data: &[u8], srgb: bool) -> Result<Texture, ImageError> {
+    let header = Header::parse(data)?;
+    let mut offset = HEADER_SIZE + header.id_length;
+
+    let color_map = match header.color_map_type {
+        0 => None,
+        _ => {
+            let entry_size = pixel_size(header.color_map_depth)?;
+            let size = header.color_map_length * entry_size;
+            let entries = data.get(offset..offset + size).ok_or(ImageError::Truncated)?;
+            offset += size;
+            Some(entries)
+        },
+    };
+
+    // The format of the texture follows the pixels, or the color map entries they index.
+    let depth = match (header.image_type & !RLE, color_map) {
+        (COLOR_MAPPED, Some(_)) if matches!(header.pixel_depth, 8 | 16) => header.color_map_depth,
+        (TRUE_COLOR, _) if header.pixel_depth != 8 => header.pixel_depth,
+        (GRAYSCALE, _) if header.pixel_depth == 8 => 8,
+        (COLOR_MAPPED | TRUE_COLOR | GRAYSCALE, _) => return Err(ImageError::UnsupportedPixelDepth(header.pixel_depth)),
+        _ => return Err(ImageError::UnsupportedImageType(header.image_type)),
+    };
+    // Without alpha bits in the descriptor, the fourth byte of 32-bit pixels is often garbage.
+    let has_alpha = matches!(depth, 16 | 32) && header.descriptor & ALPHA_BITS != 0;
+    let format = match (depth, has_alpha, srgb) {
+        (8, _, _) => TextureFormat::R8,
+        (_, false, false) => TextureFormat::RGB8,
+        (_, false, true) => TextureFormat::SRGB8,
+        (_, true, false) => TextureFormat::RGBA8,
+        (_, true, true) => TextureFormat::SRGB8_A8,
+    };
+
+    // Read the pixels before allocating the texture, so a truncated file can't allocate the declared size.
+    let pixels = data.get(offset..).ok_or(ImageError::Truncated)?;
+    let count = (header.width * header.height) as usize;
+    let elements = match header.image_type & RLE {
+        0 => read_raw(pixels, count, pixel_size(header.pixel_depth)?)?,
+        _ => read_rle(pixels, count, pixel_size(header.pixel_depth)?)?,
+    };
+    let mut texture = Texture::new(format, header.width, header.height)?;
+
+    // True-color images may carry a color map, only color-mapped pixels use it.
+    let color_map = color_map.filter(|_| header.image_type & !RLE == COLOR_MAPPED);
+    let entry_size = pixel_size(depth)?;
+    let channels = format.pixel_size();
+    for (i, element) in elements.into_iter().enumerate() {
+        let color = match color_map {
+            Some(entries) => {
+                let index = match element.len() {
+                    1 => element[0] as usize,
+                    _ => u16::from_le_bytes([element[0], element[1]]) as usize,
+                };
+                let entry = index.checked_sub(header.color_map_first)
+                    .and_then(|entry| entries.get(entry * entry_size..(entry + 1) * entry_size))
+                    .ok_or(ImageError::InvalidColorIndex(index as u32))?;
+                decode_pixel(entry)
+            },
+            None => decode_pixel(element),
+        };
+
+        let (column, row) = (i as u32 % header.width, i as u32 / header.width);
+        let x = match header.descriptor & RIGHT_TO_LEFT {
+            0 => column,
+            _ => header.width - 1 - column,
+        };
+        let y = match header.descriptor & TOP_TO_BOTTOM {
+            0 => row,
+            _ => header.height - 1 - row,
+        };
+        let offset = (x + y * header.width) as usize * channels;
+        texture.pixels[offset..offset + channels].copy_from_slice(&color[..channels]);
+    }
+    Ok(texture)
+}
+
+pub fn load<P: AsRef<Path>>(path: P, srgb: bool) -> Result<Texture, ImageError> {
+    decode(&fs::read(path)?, srgb)
+}
+
+/*
+ * Encode the base level of a texture to a TGA image with the origin at the
+ * bottom left, RLE compressed if `rle` is set. R8 and other single-channel
+ * textures are stored as 8-bit grayscale, RGB8 and SRGB8 as 24-bit
+ * true-color and everything else as 32-bit true-color with alpha. The bytes
+ * of 8-bit formats are stored as is, other formats are converted with
+ * Texture::texel_fetch().
+ */
+pub fn encode(texture: &Texture, rle: bool) -> Result<Vec<u8>, ImageError> {
+    let (width, height) = texture.get_shape();
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(ImageError::TooLarge { width, height });
+    }
+
+    let (image_type, pixel_depth) = match texture.format {
+        TextureFormat::R8 | TextureFormat::R16F | TextureFormat::R32F | TextureFormat::DEPTH_FLOAT
+            | TextureFormat::STENCIL8 | TextureFormat::BC4 => (GRAYSCALE, 8),
+        TextureFormat::RGB8 | TextureFormat::SRGB8 => (TRUE_COLOR, 24),
+        _ => (TRUE_COLOR, 32),
+    };
+    let size = pixel_size(pixel_depth)?;
+    let mut pixels = Vec::with_capacity((width * height) as usize * size);
+    for y in 0..height {
+        for x in 0..width {
+            let color = match texture.format {
+                TextureFormat::R8 | TextureFormat::RGB8 | TextureFormat::SRGB8
+                    | TextureFormat::RGBA8 | TextureFormat::SRGB8_A8 => {
+                    let channels = texture.format.pixel_size();
+                    let offset = (x + y * width) as usize * channels;
+                    let mut color = [0, 0, 0, 255];
+                    color[..channels].copy_from_slice(&texture.pixels[offset..offset + channels]);
+                    color
+                },
+                _ => {
+                    let value = texture.texel_fetch(x, y, 0);
+                    [unorm8(value.x), unorm8(value.y), unorm8(value.z), unorm8(value.w)]
+                },
+            };
+            match size {
+                1 => pixels.push(color[0]),
+                3 => pixels.extend_from_slice(&[color[2], color[1], color[0]]),
+                _ => pixels.extend_from_slice(&[color[2], color[1], color[0], color[3]]),
+            }
+        }
+    }
+
+    let mut data = vec![0; HEADER_SIZE];
+    data[2] = if rle { image_type | RLE } else { image_type };
+    data[12..14].copy_from_slice(&(width as u16).to_le_bytes());
+    data[14..16].copy_from_slice(&(height as u16).to_le_bytes());
+    data[16] = pixel_depth;
+    data[17] = if pixel_depth == 32 { 8 } else { 0 };
+    match rle {
+        true => {
+            for row in pixels.chunks_exact(width as usize * size) {
+                write_rle(&mut data, row, size);
+            }
+        },
+        false => data.extend_from_slice(&pixels),
+    }
+    // No extension or developer area.
+    data.extend_from_slice(&[0; 8]);
+    data.extend_from_slice(FOOTER_SIGNATURE);
+    Ok(data)
+}
+
+pub fn save<P: AsRef<Path>>(path: P, texture: &Texture, rle: bool) -> Result<(), ImageError> {
+    fs::write(path, encode(texture, rle)?)?;
+    Ok(())
+}
+
+// Encode an attachment of a framebuffer, see encode().
+pub fn encode_attachment(framebuffer: &FrameBuffer, attachment: AttachmentType, rle: bool) -> Result<Vec<u8>, ImageError> {
+    if let AttachmentType::Color(index) = attachment {
+        if index >= MAX_COLOR_ATTACHMENTS {
+            return Err(RenderError::InvalidAttachment(attachment).into());
+        }
+    }
+    match framebuffer.get_attachment(attachment) {
+        Some(texture) => encode(texture, rle),
+        None => Err(RenderError::MissingAttachment(attachment).into()),
+    }
+}
+
+pub fn save_attachment<P: AsRef<Path>>(path: P, framebuffer: &FrameBuffer, attachment: AttachmentType,
+    rle: bool) -> Result<(), ImageError> {
+    fs::write(path, encode_attachment(framebuffer, attachment, rle)?)?;
+    Ok(())
+}
+
+// Number of bytes of a pixel or a color map entry of `depth` bits.
+#[inline]
+fn pixel_size(depth: u8) -> Result<usize, ImageError> {
+    match depth {
+        8 => Ok(1),
+        15 | 16 => Ok(2),
+        24 => Ok(3),
+        32 => Ok(4),
+        _ => Err(ImageError::UnsupportedPixelDepth(depth)),
+    }
+}
+
+// RGBA of a grayscale, BGR555 (with an alpha bit), BGR or BGRA pixel.
+fn decode_pixel(bytes: &[u8]) -> [u8; 4] {
+    match bytes.len() {
+        1 => [bytes[0], bytes[0], bytes[0], 255],
+        2 => {
+            let pixel = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let expand = |shift: u16| {
+                let value = ((pixel >> shift) & 0x1f) as u8;
+                (value << 3) | (value >> 2)
+            };
+            let alpha = if pixel & 0x8000 != 0 { 255 } else { 0 };
+            [expand(10), expand(5), expand(0), alpha]
+        },
+        3 => [bytes[2], bytes[1], bytes[0], 255],
+        _ => [bytes[2], bytes[1], bytes[0], bytes[3]],
+    }
+}
+
+#[inline]
+fn unorm8(value: f32) -> u8 {
+    (f32_clamp01(value) * 255. + 0.5) as u8
+}
+
+fn read_raw(data: &[u8], count: usize, size: usize) -> Result<Vec<&[u8]>, ImageError> {
+    let data = data.get(..count * size).ok_or(ImageError::Truncated)?;
+    Ok(data.chunks_exact(size).collect())
+}
+
+/*
+ * Read `count` pixels of RLE packets. The header byte of a packet holds the
+ * number of pixels minus one, with the high bit set a single pixel is
+ * repeated, otherwise the pixels follow. Packets may cross rows.
+ */
+fn read_rle(data: &[u8], count: usize, size: usize) -> Result<Vec<&[u8]>, ImageError> {
+    // Bound the initial capacity by the data, the declared count may be bogus.
+    let mut elements = Vec::with_capacity(usize::min(count, data.len()));
+    let mut offset = 0;
+    while elements.len() < count {
+        let packet = *data.get(offset).ok_or(ImageError::Truncated)?;
+        let length = (packet & 0x7f) as usize + 1;
+        offset += 1;
+        if elements.len() + length > count {
+            return Err(ImageError::Truncated);
+        }
+        match packet & 0x80 {
+            0 => {
+                let pixels = data.get(offset..offset + length * size).ok_or(ImageError::Truncated)?;
+                elements.extend(pixels.chunks_exact(size));
+                offset += length * size;
+            },
+            _ => {
+                let pixel = data.get(offset..offset + size).ok_or(ImageError::Truncated)?;
+                elements.extend(std::iter::repeat_n(pixel, length));
+                offset += size;
+            },
+        }
+    }
+    Ok(elements)
+}
+
+// Append a row of pixels as RLE packets of at most 128 pixels, repeated pixels become run packets.
+fn write_rle(data: &mut Vec<u8>, row: &[u8], size: usize) {
+    let pixels: Vec<&[u8]> = row.chunks_exact(size).collect();
+    let run_length = |start: usize| {
+        pixels[start..].iter().take(128).take_while(|&&pixel| pixel == pixels[start]).count()
+    };
+
+    let mut start = 0;
+    while start < pixels.len() {
+        let length = run_length(start);
+        if length > 1 {
+            data.push(0x80 | (length - 1) as u8);
+            data.extend_from_slice(pixels[start]);
+            start += length;
+            continue;
+        }
+        let mut end = start + 1;
+        while end < pixels.len() && end - start < 128 && run_length(end) == 1 {
+            end += 1;
+        }
+        data.push((end - start - 1) as u8);
+        for pixel in &pixels[start..end] {
+            data.extend_from_slice(pixel);
+        }
+        start = end;
+    }
+}
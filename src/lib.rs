@@ -1,2 +1,4 @@
 pub mod math;
 pub mod graphics;
+pub mod error;
+pub mod image;
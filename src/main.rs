@@ -1,5 +1,3 @@
-use foolrenderer_rs::*;
-
 fn main() {
     println!("Hello, world!");
 }
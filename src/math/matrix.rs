@@ -62,6 +62,13 @@ macro_rules! mat4_zero {
 // Mat3 implements
 // ==================================================
 
+impl Default for Mat3 {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Mat3 {
     #[inline]
     pub fn new() -> Self {
@@ -168,6 +175,13 @@ impl ops::IndexMut<usize> for Mat3 {
 // Mat4 implements
 // ==================================================
 
+impl Default for Mat4 {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Mat4 {
     #[inline]
     pub fn new() -> Self {
@@ -10,4 +10,5 @@ pub mod preclude {
 }
 
 #[cfg(test)]
+#[allow(unused_assignments)]
 mod test;
@@ -1,4 +1,4 @@
-pub const PI: f32 = 3.1415926535897932;
+pub const PI: f32 = std::f32::consts::PI;
 pub const SMALL_ABSOLUTE_F32: f32 = 0.000_000_01;
 
 #[inline]
@@ -1,20 +1,20 @@
 use core::ops;
 use crate::math::utility;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
     pub z: f32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vec4 {
     pub x: f32,
     pub y: f32,
@@ -105,9 +105,9 @@ impl Vec2 {
     pub fn normalize(self) -> Self {
         let squared_magnitude = self.magnitude_squared();
         if squared_magnitude == 0. {
-            return vec2_zero!();
+            vec2_zero!()
         } else if (squared_magnitude - 1.0).abs() < utility::SMALL_ABSOLUTE_F32 {
-            return self;
+            self
         } else {
             self * (1. / squared_magnitude.sqrt())
         }
@@ -128,15 +128,6 @@ impl From<&[f32; 2]> for Vec2 {
         Self { x: a[0], y: a[1] }
     }
 }
-impl Clone for Vec2 {
-    #[inline]
-    fn clone(&self) -> Self {
-        Self { x: self.x, y: self.y }
-    }
-}
-
-impl Copy for Vec2 {}
-
 impl ops::Add for Vec2 {
     type Output = Self;
 
@@ -259,9 +250,9 @@ impl Vec3 {
     pub fn normalize(self) -> Self {
         let squared_magnitude = self.magnitude_squared();
         if squared_magnitude == 0. {
-            return vec3_zero!();
+            vec3_zero!()
         } else if (squared_magnitude - 1.0).abs() < utility::SMALL_ABSOLUTE_F32 {
-            return self;
+            self
         } else {
             self * (1. / squared_magnitude.sqrt())
         }
@@ -284,15 +275,6 @@ impl From<&[f32; 3]> for Vec3 {
     }
 }
 
-impl Clone for Vec3 {
-    #[inline]
-    fn clone(&self) -> Self {
-        Self { x: self.x, y: self.y , z: self.z }
-    }
-}
-
-impl Copy for Vec3 {}
-
 impl ops::Add for Vec3 {
     type Output = Self;
     
@@ -405,9 +387,9 @@ impl Vec4 {
     pub fn normalize(self) -> Self {
         let squared_magnitude = self.magnitude_squared();
         if squared_magnitude == 0. {
-            return vec4_zero!();
+            vec4_zero!()
         } else if (squared_magnitude - 1.0).abs() < utility::SMALL_ABSOLUTE_F32 {
-            return self;
+            self
         } else {
             self * (1. / squared_magnitude.sqrt())
         }
@@ -431,15 +413,6 @@ impl From<&[f32; 4]> for Vec4 {
     }
 }
 
-impl Clone for Vec4 {
-    #[inline]
-    fn clone(&self) -> Self {
-        Self { x: self.x, y: self.y , z: self.z, w: self.w }
-    }
-}
-
-impl Copy for Vec4 {}
-
 impl ops::Add for Vec4 {
     type Output = Self;
 